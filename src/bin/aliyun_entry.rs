@@ -1,5 +1,10 @@
+use gcores_rss::manifest::{self, Event};
+use gcores_rss::store::{self, Sts};
+use gcores_rss::Error;
 use log::LevelFilter;
 use simple_logger::SimpleLogger;
+use std::convert::Infallible;
+use warp::http::StatusCode;
 use warp::Filter;
 
 #[tokio::main]
@@ -15,107 +20,46 @@ async fn main() {
         .and(warp::header::<String>("x-fc-access-key-secret"))
         .and(warp::header::<String>("x-fc-security-token"))
         .and(warp::body::bytes())
-        .map(|id, secret, token, data: bytes::Bytes| {
-            let req: req::Request = serde_json::from_slice(&data).unwrap();
-            (id, secret, token, req)
-        })
-        .untuple_one()
-        .and_then(|id, secret, token, req: req::Request| async move {
-            let req::Request {
-                oss_param,
-                param,
-                channel,
-            } = req;
-            let xml: String = gcores_rss::get(param, channel).await.unwrap();
-
-            let sts = req::STS { id, secret, token };
-
-            Ok::<(req::OssParam, req::STS, String), warp::reject::Rejection>((oss_param, sts, xml))
-        })
-        .untuple_one()
-        .and_then(|param, sts, xml| async move {
-            let resp = req::save_to_oss(param, sts, xml);
-            Ok::<String, warp::reject::Rejection>(resp)
-        });
+        .and_then(invoke);
 
     warp::serve(route).run(([0, 0, 0, 0], 9000)).await;
 }
 
-mod req {
-    use gcores_rss::{Channel, Param};
-    use serde::Deserialize;
-    use sloppy_auth::{aliyun, util};
-
-    pub struct STS {
-        pub id: String,
-        pub secret: String,
-        pub token: String,
-    }
-
-    pub fn save_to_oss(param: OssParam, sts: STS, xml: String) -> String {
-        let OssParam {
-            endpoint,
-            bucket,
-            key,
-            acl,
-            service: _,
-            content_type,
-        } = param;
-        let acl1 = acl.unwrap_or("public-read".to_string());
-        let content_type1 = content_type.unwrap_or("application/xml".to_string());
-        let req_url = format!("http://{}.{}/{}", bucket.clone(), endpoint.clone(), key);
-
-        let STS { id, secret, token } = sts;
-
-        let format_date = util::get_date();
-
-        let secret_header = ("x-oss-security-token".to_string(), token);
-
-        let x_oss_acl = "x-oss-object-acl";
-
-        let body_md5 = util::md5(xml.as_bytes().to_vec());
-
-        let auth = aliyun::oss::Client {
-            verb: "PUT".to_string(),
-            oss_headers: vec![secret_header.clone(), (x_oss_acl.to_string(), acl1.clone())],
-            bucket: bucket.clone(),
-            date: Some(format_date.clone()),
-            content_type: content_type1.clone(),
-            content_md5: body_md5.clone(),
-            key,
-            key_id: id,
-            key_secret: secret,
-        };
-
-        ureq::put(&req_url)
-            .set("authorization", auth.make_authorization().as_str())
-            .set("Host", &format!("{}.{}", bucket, endpoint))
-            .set("Content-Type", content_type1.as_str())
-            .set("Content-MD5", body_md5.as_str())
-            .set(&secret_header.0, &secret_header.1)
-            .set(x_oss_acl, &acl1)
-            .set("date", &format_date.clone())
-            .send_bytes(xml.as_bytes())
-            .expect("oss send xml failed")
-            .into_string()
-            .expect("oss response to_string failed")
-    }
-
-    #[derive(Deserialize)]
-    pub struct Request {
-        #[serde(rename = "storage_param")]
-        pub oss_param: OssParam,
-        pub channel: Channel,
-        pub param: Param,
-    }
+async fn invoke(
+    id: String,
+    secret: String,
+    token: String,
+    data: bytes::Bytes,
+) -> Result<warp::reply::WithStatus<String>, Infallible> {
+    let sts = Sts { id, secret, token };
+    let reply = match fetch_save(sts, &data).await {
+        Ok((resp, status)) => warp::reply::with_status(
+            resp,
+            StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        ),
+        Err(e) => {
+            log::error!("{}", e);
+            let status =
+                StatusCode::from_u16(e.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            warp::reply::with_status(e.to_string(), status)
+        }
+    };
+    Ok(reply)
+}
 
-    #[derive(Deserialize, Debug)]
-    pub struct OssParam {
-        pub service: String,
-        pub bucket: String,
-        pub key: String,
-        pub acl: Option<String>,
-        pub content_type: Option<String>,
-        pub endpoint: String,
+//reply body and status, a manifest answers with its reports and the worst status
+async fn fetch_save(sts: Sts, data: &[u8]) -> Result<(String, u16), Error> {
+    match Event::from_slice(data)? {
+        Event::One(feed) => {
+            let store = store::open(feed.storage_param, Some(sts))?;
+            let outcome = gcores_rss::publish(feed.param, feed.channel, store).await?;
+            Ok((outcome.to_string(), 200))
+        }
+        Event::Many(feeds) => {
+            let reports = manifest::publish_all(feeds, Some(sts)).await;
+            let status = reports.iter().map(|r| r.status).max().unwrap_or(200);
+            let body = serde_json::to_string(&reports)?;
+            Ok((body, status))
+        }
     }
-} /* req */
+}
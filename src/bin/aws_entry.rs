@@ -1,32 +1,22 @@
-use gcores_rss::{get, Channel, Param};
+use gcores_rss::config::Defaults;
+use gcores_rss::manifest::{self, Event, Report};
+use gcores_rss::store;
+use gcores_rss::{publish, Error as FeedError, Outcome};
 use lambda_runtime::{handler_fn, Context, Error};
 use log::LevelFilter;
-use rusoto_core::{ByteStream, Region};
-use rusoto_s3::{PutObjectRequest, S3Client, S3};
-use serde::{Deserialize, Serialize};
-use simple_error::SimpleError;
+use serde::Serialize;
+use serde_json::Value;
 use simple_logger::SimpleLogger;
-use std::error::Error as SError;
-
-#[derive(Deserialize)]
-struct Request {
-    #[serde(rename = "storage_param")]
-    s3_param: S3Param,
-    channel: Channel,
-    param: Param,
-}
-
-#[derive(Deserialize)]
-struct S3Param {
-    bucket: String,
-    key: String,
-    acl: Option<String>,
-    content_type: Option<String>,
-}
 
 #[derive(Serialize)]
 struct Response {
     req_id: String,
+    //outcome of a single feed event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<Outcome>,
+    //per feed results of a manifest event
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    feeds: Vec<Report>,
 }
 
 #[tokio::main]
@@ -40,39 +30,108 @@ async fn main() -> Result<(), Error> {
     Ok(())
 }
 
-fn to_simple(e: Box<dyn SError>) -> SimpleError {
-    SimpleError::new(e.to_string())
-}
-
-pub(crate) async fn fetch_save(event: Request, ctx: Context) -> Result<Response, SimpleError> {
-    let Request {
-        s3_param,
-        param,
-        channel,
-    } = event;
-    let xml: String = get(param, channel).await.map_err(to_simple)?;
-    save_to_s3(s3_param, xml.to_string()).map_err(to_simple)?;
+pub(crate) async fn fetch_save(
+    event: serde_json::Value,
+    ctx: Context,
+) -> Result<Response, FeedError> {
+    let event = legacy_storage(Defaults::load()?.apply(event));
+    let (status, feeds) = match Event::from_value(event)? {
+        Event::One(feed) => {
+            let store = store::open(feed.storage_param, None)?;
+            let status = publish(feed.param, feed.channel, store).await?;
+            (Some(status), vec![])
+        }
+        Event::Many(feeds) => {
+            let reports = manifest::publish_all(feeds, None).await;
+            check(&reports)?;
+            (None, reports)
+        }
+    };
     Ok(Response {
         req_id: ctx.request_id,
+        status,
+        feeds,
     })
 }
 
-fn save_to_s3(param: S3Param, val: String) -> Result<(), Box<dyn SError>> {
-    let S3Param {
-        acl,
-        bucket,
-        key,
-        content_type,
-    } = param;
-    S3Client::new(Region::UsEast1)
-        .put_object(PutObjectRequest {
-            acl,
-            body: Some(ByteStream::from(val.into_bytes())),
-            bucket,
-            key,
-            content_type,
-            ..Default::default()
-        })
-        .sync()?;
-    Ok(())
+//a manifest with a failed feed fails the invocation, as the aliyun entry answers with
+//the worst status; the message carries every report
+fn check(reports: &[Report]) -> Result<(), FeedError> {
+    let worst = reports.iter().map(|r| r.status).max().unwrap_or(200);
+    if worst == 200 {
+        return Ok(());
+    }
+    let failed = reports.iter().filter(|r| r.status != 200).count();
+    let msg = format!(
+        "{} of {} feeds failed: {}",
+        failed,
+        reports.len(),
+        serde_json::to_string(reports)?
+    );
+    Err(match worst {
+        400 => FeedError::config(msg),
+        500 => FeedError::Xml(msg.into()),
+        _ => FeedError::storage(msg),
+    })
+}
+
+//events written before storage_param.service existed only stored to s3
+fn legacy_storage(mut event: Value) -> Value {
+    fn default_service(feed: &mut Value) {
+        if let Some(param) = feed.get_mut("storage_param").and_then(Value::as_object_mut) {
+            param.entry("service").or_insert_with(|| Value::from("s3"));
+        }
+    }
+
+    match event.get_mut("feeds").and_then(Value::as_array_mut) {
+        Some(feeds) => feeds.iter_mut().for_each(default_service),
+        None => default_service(&mut event),
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, legacy_storage};
+    use gcores_rss::manifest::Report;
+    use gcores_rss::Outcome;
+    use serde_json::json;
+
+    #[test]
+    fn service_defaults_to_s3() {
+        let event = legacy_storage(json!({
+            "storage_param": { "bucket": "feeds", "key": "gadio.xml" }
+        }));
+        assert_eq!(event["storage_param"]["service"], "s3");
+
+        let manifest = legacy_storage(json!({ "feeds": [
+            { "storage_param": { "bucket": "feeds", "key": "a.xml" } },
+            { "storage_param": { "service": "local", "dir": "/tmp", "key": "b.xml" } }
+        ]}));
+        assert_eq!(manifest["feeds"][0]["storage_param"]["service"], "s3");
+        assert_eq!(manifest["feeds"][1]["storage_param"]["service"], "local");
+    }
+    #[test]
+    fn failed_feed_fails_manifest() {
+        let report = |name: &str, status: u16| Report {
+            name: name.to_string(),
+            outcome: match status {
+                200 => Some(Outcome::Published),
+                _ => None,
+            },
+            error: match status {
+                200 => None,
+                _ => Some(format!("{} failed", name)),
+            },
+            status,
+        };
+        assert!(check(&[report("a", 200), report("b", 200)]).is_ok());
+
+        let err = check(&[report("a", 200), report("b", 502), report("c", 400)]).unwrap_err();
+        assert_eq!(err.status(), 502);
+        let msg = err.to_string();
+        assert!(msg.contains("2 of 3 feeds failed"), "{}", msg);
+        assert!(msg.contains(r#""name":"a""#), "{}", msg);
+        assert!(msg.contains("c failed"), "{}", msg);
+    }
 }
@@ -0,0 +1,219 @@
+use gcores_rss::config::Defaults;
+use gcores_rss::validate;
+use gcores_rss::{stream, Channel, Error, Format, Param};
+use serde::Deserialize;
+use std::io::{self, BufWriter, Read};
+use std::{env, fs, process};
+
+const USAGE: &str = "usage: cli_entry [options]
+  --event FILE    json event as in event-example.json, - for stdin
+  --config FILE   toml or json defaults under the event, GCORES_RSS_CONFIG otherwise
+  --output FILE   write the feed to FILE instead of stdout
+  --format NAME   output format: rss (default), atom or json
+  --url URL       api url, default https://www.gcores.com/gapi/v1/radios
+  --start N       offset of the first episode
+  --size N        episodes per page
+  --pages N       fetch at most N pages
+  --limit N       number of episodes to fetch
+  --until TIME    fetch episodes published since rfc3339 TIME
+  --delay MS      milliseconds between page requests";
+
+const DEFAULT_URL: &str = "https://www.gcores.com/gapi/v1/radios";
+const DEFAULT_SIZE: u16 = 10;
+
+//same shape as the function events, storage_param is ignored
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    channel: Channel,
+    #[serde(default)]
+    param: Param,
+}
+
+#[derive(Default)]
+struct Args {
+    event: Option<String>,
+    config: Option<String>,
+    output: Option<String>,
+    format: Option<Format>,
+    url: Option<String>,
+    start: Option<u32>,
+    size: Option<u16>,
+    pages: Option<u32>,
+    limit: Option<u32>,
+    until: Option<String>,
+    delay: Option<u64>,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = run(args).await {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_args(mut it: impl Iterator<Item = String>) -> Result<Args, Error> {
+    fn num<T: std::str::FromStr>(flag: &str, v: String) -> Result<T, Error> {
+        v.parse()
+            .map_err(|_| Error::config(format!("{} expects a number, got {}", flag, v)))
+    }
+
+    let mut args = Args::default();
+    while let Some(flag) = it.next() {
+        if flag == "-h" || flag == "--help" {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        let v = it
+            .next()
+            .ok_or_else(|| Error::config(format!("{} expects a value", flag)))?;
+        match flag.as_str() {
+            "--event" => args.event = Some(v),
+            "--config" => args.config = Some(v),
+            "--output" => args.output = Some(v),
+            "--format" => args.format = Some(v.parse()?),
+            "--url" => args.url = Some(v),
+            "--start" => args.start = Some(num(&flag, v)?),
+            "--size" => args.size = Some(num(&flag, v)?),
+            "--pages" => args.pages = Some(num(&flag, v)?),
+            "--limit" => args.limit = Some(num(&flag, v)?),
+            "--until" => args.until = Some(v),
+            "--delay" => args.delay = Some(num(&flag, v)?),
+            _ => return Err(Error::config(format!("unknown option {}", flag))),
+        }
+    }
+    Ok(args)
+}
+
+fn read_event(path: &str) -> Result<serde_json::Value, Error> {
+    let mut data = String::new();
+    if path == "-" {
+        io::stdin()
+            .read_to_string(&mut data)
+            .map_err(|e| Error::Config(e.into()))?;
+    } else {
+        data =
+            fs::read_to_string(path).map_err(|e| Error::config(format!("read {}: {}", path, e)))?;
+    }
+    serde_json::from_str(&data).map_err(|e| Error::config(format!("event {}: {}", path, e)))
+}
+
+async fn run(args: Args) -> Result<(), Error> {
+    let event = match &args.event {
+        Some(path) => read_event(path)?,
+        None => serde_json::json!({}),
+    };
+    let event = Defaults::load_from(args.config.as_deref())?.apply(event);
+    let Request { channel, mut param } =
+        serde_json::from_value(event).map_err(|e| Error::Config(e.into()))?;
+    if param.url.is_empty() {
+        param.url = DEFAULT_URL.to_string();
+    }
+    if param.size == 0 {
+        param.size = DEFAULT_SIZE;
+    }
+
+    if let Some(url) = args.url {
+        param.url = url;
+    }
+    if let Some(start) = args.start {
+        param.start = start;
+    }
+    if let Some(size) = args.size {
+        param.size = size;
+    }
+    if args.pages.is_some() {
+        param.pages = args.pages;
+    }
+    if args.limit.is_some() {
+        param.limit = args.limit;
+    }
+    if args.until.is_some() {
+        param.until = args.until;
+    }
+    if args.delay.is_some() {
+        param.delay = args.delay;
+    }
+    if let Some(format) = args.format {
+        param.format = format;
+    }
+
+    //every issue goes to stderr, the errors among them stop the preview as they stop a publish
+    for issue in validate::channel(&channel) {
+        eprintln!("{}", issue);
+    }
+
+    match args.output {
+        //written next to `path` and renamed over it once complete,
+        //a failed fetch or write leaves the previous file as it was
+        Some(path) => {
+            let tmp = format!("{}.tmp", path);
+            let written = match fs::File::create(&tmp) {
+                Ok(file) => stream(param, channel, &mut BufWriter::new(file)).await,
+                Err(e) => Err(Error::storage(format!("write {}: {}", tmp, e))),
+            }
+            .and_then(|_| {
+                fs::rename(&tmp, &path)
+                    .map_err(|e| Error::storage(format!("rename {} to {}: {}", tmp, path, e)))
+            });
+            if written.is_err() {
+                let _ = fs::remove_file(&tmp);
+            }
+            written
+        }
+        None => stream(param, channel, &mut BufWriter::new(io::stdout())).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_args, Args};
+    use gcores_rss::{Error, Format};
+
+    fn parse(args: &[&str]) -> Result<Args, Error> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn flags() {
+        let args = parse(&[
+            "--event",
+            "-",
+            "--output",
+            "gadio.xml",
+            "--format",
+            "atom",
+            "--size",
+            "20",
+            "--pages",
+            "3",
+            "--delay",
+            "500",
+        ])
+        .unwrap();
+        assert_eq!(args.event.as_deref(), Some("-"));
+        assert_eq!(args.output.as_deref(), Some("gadio.xml"));
+        assert_eq!(args.format, Some(Format::Atom));
+        assert_eq!(args.size, Some(20));
+        assert_eq!(args.pages, Some(3));
+        assert_eq!(args.delay, Some(500));
+        assert_eq!(args.limit, None);
+    }
+
+    #[test]
+    fn bad_flags() {
+        assert!(matches!(parse(&["--verbose", "1"]), Err(Error::Config(_))));
+        assert!(matches!(parse(&["--size", "ten"]), Err(Error::Config(_))));
+        assert!(matches!(parse(&["--size", "70000"]), Err(Error::Config(_))));
+        assert!(matches!(parse(&["--pages"]), Err(Error::Config(_))));
+        assert!(parse(&["--format", "html"]).is_err());
+    }
+}
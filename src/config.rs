@@ -0,0 +1,257 @@
+use crate::error::Error;
+use serde_json::{Map, Value};
+use std::{env, fs};
+
+//path of a toml or json file holding event defaults
+pub const CONFIG_VAR: &str = "GCORES_RSS_CONFIG";
+//GCORES_RSS_CHANNEL__TITLE sets channel.title
+pub const ENV_PREFIX: &str = "GCORES_RSS_";
+//settings that are numbers or booleans, their variables are read as json;
+//other values stay text unless they hold a json list or object
+const TYPED: [&str; 9] = [
+    "param.start",
+    "param.size",
+    "param.limit",
+    "param.delay",
+    "param.max_items",
+    "param.filter.min_duration",
+    "param.filter.max_duration",
+    "channel.locked",
+    "storage_param.https",
+];
+
+//settings an event falls back to, in the shape of a single feed event
+#[derive(Debug, Default, Clone)]
+pub struct Defaults {
+    value: Value,
+}
+
+impl Defaults {
+    //the file named by GCORES_RSS_CONFIG, overridden by GCORES_RSS_* variables
+    pub fn load() -> Result<Self, Error> {
+        Defaults::load_from(None)
+    }
+
+    //as `load`, `path` takes the place of GCORES_RSS_CONFIG
+    pub fn load_from(path: Option<&str>) -> Result<Self, Error> {
+        let path = path
+            .map(str::to_string)
+            .or_else(|| env::var(CONFIG_VAR).ok());
+        let mut defaults = match path {
+            Some(path) => Defaults::from_file(&path)?,
+            None => Defaults::default(),
+        };
+        defaults.merge(from_vars(env::vars()));
+        Ok(defaults)
+    }
+
+    //toml when the name ends with .toml, json otherwise
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        let data =
+            fs::read_to_string(path).map_err(|e| Error::config(format!("read {}: {}", path, e)))?;
+        let value = if path.ends_with(".toml") {
+            toml::from_str(&data).map_err(|e| Error::config(format!("{}: {}", path, e)))?
+        } else {
+            serde_json::from_str(&data).map_err(|e| Error::config(format!("{}: {}", path, e)))?
+        };
+        Ok(Defaults { value })
+    }
+
+    pub fn merge(&mut self, over: Value) {
+        merge(&mut self.value, over);
+    }
+
+    //the event over the defaults, every feed of a manifest gets them
+    pub fn apply(&self, mut event: Value) -> Value {
+        if let Some(Value::Array(feeds)) = event.get_mut("feeds") {
+            for feed in feeds.iter_mut() {
+                *feed = self.over(feed.take());
+            }
+            return event;
+        }
+        self.over(event)
+    }
+
+    fn over(&self, feed: Value) -> Value {
+        let mut merged = self.value.clone();
+        merge(&mut merged, feed);
+        merged
+    }
+}
+
+//objects are merged key by key, anything else in `over` replaces `base`; null keeps `base`
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (_, Value::Null) => {}
+        (Value::Object(base), Value::Object(over)) => {
+            for (k, v) in over {
+                merge(base.entry(k).or_insert(Value::Null), v);
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+fn from_vars(vars: impl Iterator<Item = (String, String)>) -> Value {
+    let mut value = Value::Object(Map::new());
+    for (name, raw) in vars {
+        let path = match name.strip_prefix(ENV_PREFIX) {
+            Some(path) if name != CONFIG_VAR => path.to_lowercase(),
+            _ => continue,
+        };
+        let typed = TYPED.contains(&path.replace("__", ".").as_str());
+        let leaf = match typed || raw.trim_start().starts_with(['[', '{']) {
+            true => serde_json::from_str(&raw).unwrap_or(Value::String(raw)),
+            false => Value::String(raw),
+        };
+        let nested = path.rsplit("__").fold(leaf, |acc, key| {
+            let mut obj = Map::new();
+            obj.insert(key.to_string(), acc);
+            Value::Object(obj)
+        });
+        merge(&mut value, nested);
+    }
+    value
+}
+
+const CHANNEL_REQUIRED: [&str; 4] = ["title", "link", "media_base_url", "web_base_url"];
+const PARAM_REQUIRED: [&str; 2] = ["url", "size"];
+
+//names every required setting missing from a feed event at once, `path` prefixes the names
+pub fn validate(feed: &Value, path: &str) -> Result<(), Error> {
+    let set = |section: &str, key: &str| match feed.get(section).and_then(|s| s.get(key)) {
+        None | Some(Value::Null) => false,
+        Some(Value::String(s)) => !s.is_empty(),
+        Some(Value::Number(n)) => n.as_u64() != Some(0),
+        Some(_) => true,
+    };
+
+    let storage_required: &[&str] = match feed
+        .get("storage_param")
+        .and_then(|s| s.get("service"))
+        .and_then(Value::as_str)
+    {
+        Some("s3") => &["bucket", "key"],
+        Some("oss") => &["bucket", "key", "endpoint"],
+        Some("local") => &["dir", "key"],
+        _ => &["service"],
+    };
+
+    let missing: Vec<String> = [
+        ("channel", &CHANNEL_REQUIRED[..]),
+        ("param", &PARAM_REQUIRED[..]),
+        ("storage_param", storage_required),
+    ]
+    .iter()
+    .flat_map(|(section, keys)| {
+        keys.iter()
+            .filter(move |key| !set(section, key))
+            .map(move |key| format!("{}{}.{}", path, section, key))
+    })
+    .collect();
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(Error::config(format!(
+        "missing {}, set them in the event, the {} file or {}* variables",
+        missing.join(", "),
+        CONFIG_VAR,
+        ENV_PREFIX
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_vars, validate, Defaults};
+    use crate::filter::Filter;
+    use serde_json::json;
+    use std::error::Error;
+    use std::{env, fs};
+
+    #[test]
+    fn event_over_defaults() -> Result<(), Box<dyn Error>> {
+        let path = env::temp_dir().join(format!("gcores_rss_config_{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            r#"
+            [channel]
+            title = "机核电台"
+            link = "https://www.gcores.com/radios"
+
+            [param]
+            url = "https://www.gcores.com/gapi/v1/radios"
+            size = 5
+            "#,
+        )?;
+        let mut defaults = Defaults::from_file(path.to_str().unwrap())?;
+        fs::remove_file(&path)?;
+        defaults.merge(from_vars(
+            vec![
+                ("GCORES_RSS_PARAM__SIZE".to_string(), "10".to_string()),
+                (
+                    "GCORES_RSS_CHANNEL__OWNER_NAME".to_string(),
+                    "2077".to_string(),
+                ),
+                (
+                    "GCORES_RSS_CHANNEL__PERSONS".to_string(),
+                    r#"[{"name": "西蒙"}]"#.to_string(),
+                ),
+                (
+                    "GCORES_RSS_CHANNEL__MEDIA_BASE_URL".to_string(),
+                    "https://alioss.gcores.com/uploads/audio/".to_string(),
+                ),
+                (
+                    "GCORES_RSS_PARAM__FILTER__MIN_DURATION".to_string(),
+                    "600".to_string(),
+                ),
+                (
+                    "GCORES_RSS_PARAM__FILTER__INCLUDE".to_string(),
+                    r#"["Gadio"]"#.to_string(),
+                ),
+                ("GCORES_RSS_CONFIG".to_string(), "ignored".to_string()),
+                ("HOME".to_string(), "/root".to_string()),
+            ]
+            .into_iter(),
+        ));
+
+        let event = defaults.apply(json!({
+            "channel": { "title": "Gadio", "web_base_url": "https://www.gcores.com/radios/" },
+            "param": { "limit": 20 },
+            "storage_param": { "service": "local", "dir": "/tmp", "key": "rss.xml" }
+        }));
+        assert_eq!(event["channel"]["title"], "Gadio");
+        assert_eq!(event["channel"]["link"], "https://www.gcores.com/radios");
+        assert_eq!(event["param"]["size"], 10);
+        assert_eq!(event["channel"]["owner_name"], "2077");
+        assert_eq!(event["channel"]["persons"][0]["name"], "西蒙");
+        assert_eq!(event["param"]["limit"], 20);
+        let filter: Filter = serde_json::from_value(event["param"]["filter"].clone())?;
+        assert_eq!(filter.min_duration, Some(600));
+        assert_eq!(filter.include, vec!["Gadio"]);
+        assert!(event.get("config").is_none());
+        validate(&event, "")?;
+
+        let manifest = defaults.apply(json!({ "feeds": [{ "param": { "size": 3 } }] }));
+        assert_eq!(manifest["feeds"][0]["param"]["size"], 3);
+        assert_eq!(manifest["feeds"][0]["channel"]["title"], "机核电台");
+        Ok(())
+    }
+
+    #[test]
+    fn missing_fields() {
+        let err = validate(
+            &json!({
+            "channel": { "title": "t", "link": "" },
+            "param": { "url": "u", "size": 0 },
+            "storage_param": { "service": "oss", "bucket": "b" }
+            }),
+            "feeds[1].",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains(
+            "missing feeds[1].channel.link, feeds[1].channel.media_base_url, feeds[1].channel.web_base_url, feeds[1].param.size, feeds[1].storage_param.key, feeds[1].storage_param.endpoint"
+        ));
+    }
+}
@@ -0,0 +1,103 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+type Source = Box<dyn StdError + Send + Sync>;
+
+#[derive(Debug)]
+pub enum Error {
+    //gcores api unreachable or answered with an error status
+    Http(Source),
+    //api or stored body that could not be decoded
+    Decode(Source),
+    //feed document could not be written
+    Xml(Source),
+    //invalid request, param or channel settings
+    Config(Source),
+    //feed could not be read from or written to storage
+    Storage(Source),
+}
+
+impl Error {
+    pub fn config(msg: impl Into<String>) -> Self {
+        Error::Config(msg.into().into())
+    }
+
+    pub fn storage(e: impl Into<Source>) -> Self {
+        Error::Storage(e.into())
+    }
+
+    pub fn decode(e: impl Into<Source>) -> Self {
+        Error::Decode(e.into())
+    }
+
+    //http status reported by the entry points
+    pub fn status(&self) -> u16 {
+        match self {
+            Error::Config(_) => 400,
+            Error::Http(_) | Error::Decode(_) | Error::Storage(_) => 502,
+            Error::Xml(_) => 500,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "upstream http error: {}", e),
+            Error::Decode(e) => write!(f, "decode error: {}", e),
+            Error::Xml(e) => write!(f, "xml write error: {}", e),
+            Error::Config(e) => write!(f, "config error: {}", e),
+            Error::Storage(e) => write!(f, "storage error: {}", e),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Http(e)
+            | Error::Decode(e)
+            | Error::Xml(e)
+            | Error::Config(e)
+            | Error::Storage(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            Error::Decode(e.into())
+        } else {
+            Error::Http(e.into())
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Decode(e.into())
+    }
+}
+
+impl From<chrono::ParseError> for Error {
+    fn from(e: chrono::ParseError) -> Self {
+        Error::Decode(e.into())
+    }
+}
+
+impl From<quick_xml::Error> for Error {
+    fn from(e: quick_xml::Error) -> Self {
+        match e {
+            //the sink the feed is written into failed
+            quick_xml::Error::Io(e) => Error::Storage(e.into()),
+            e => Error::Xml(e.into()),
+        }
+    }
+}
+
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(e: std::string::FromUtf8Error) -> Self {
+        Error::Xml(e.into())
+    }
+}
@@ -0,0 +1,139 @@
+use crate::error::Error;
+use crate::model::api::{Radio, Response};
+use regex::Regex;
+use serde::Deserialize;
+
+//`param.filter` of a request, every set condition must hold
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    //title regexes, at least one must match
+    #[serde(default)]
+    pub include: Vec<String>,
+    //title regexes, none may match
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    //category or tag ids, at least one must be on the radio
+    #[serde(default)]
+    pub categories: Vec<String>,
+    //seconds
+    #[serde(default)]
+    pub min_duration: Option<u32>,
+    #[serde(default)]
+    pub max_duration: Option<u32>,
+}
+
+//a filter with its regexes compiled
+pub struct Matcher {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    categories: Vec<String>,
+    min_duration: Option<u32>,
+    max_duration: Option<u32>,
+}
+
+impl Filter {
+    pub fn compile(&self) -> Result<Matcher, Error> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| Regex::new(p).map_err(|e| Error::config(format!("filter {}: {}", p, e))))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Matcher {
+            include: compile(&self.include)?,
+            exclude: compile(&self.exclude)?,
+            categories: self.categories.clone(),
+            min_duration: self.min_duration,
+            max_duration: self.max_duration,
+        })
+    }
+}
+
+impl Matcher {
+    //whether the radio of `resp` passes, radios without media fail any duration bound
+    pub fn matches(&self, resp: &Response, radio: &Radio) -> bool {
+        let title = &radio.attributes.title;
+        if !self.include.is_empty() && !self.include.iter().any(|re| re.is_match(title)) {
+            return false;
+        }
+        if self.exclude.iter().any(|re| re.is_match(title)) {
+            return false;
+        }
+
+        if !self.categories.is_empty() {
+            let rels = &radio.relationships;
+            let tagged = rels
+                .category
+                .identifiers()
+                .iter()
+                .chain(rels.tags.identifiers())
+                .any(|ident| self.categories.contains(&ident.id));
+            if !tagged {
+                return false;
+            }
+        }
+
+        if self.min_duration.is_some() || self.max_duration.is_some() {
+            let duration = match resp.media_of(radio) {
+                Some(media) => media.attributes.duration as u32,
+                None => return false,
+            };
+            if self.min_duration.is_some_and(|min| duration < min)
+                || self.max_duration.is_some_and(|max| duration > max)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    //keep the matching radios of `resp`
+    pub fn retain(&self, resp: &mut Response) {
+        let keep: Vec<bool> = resp.data.iter().map(|r| self.matches(resp, r)).collect();
+        let mut keep = keep.into_iter();
+        resp.data.retain(|_| keep.next().unwrap_or(false));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filter;
+    use crate::model::api::Response;
+    use std::error::Error;
+    use std::fs;
+
+    fn titles(resp: &Response) -> Vec<&str> {
+        resp.data
+            .iter()
+            .map(|r| r.attributes.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn title_and_duration() -> Result<(), Box<dyn Error>> {
+        let json: String = fs::read_to_string("api_response.json")?;
+        let filter = |value: serde_json::Value| -> Result<Response, Box<dyn Error>> {
+            let mut resp: Response = serde_json::from_str(&json)?;
+            let filter: Filter = serde_json::from_value(value)?;
+            filter.compile()?.retain(&mut resp);
+            Ok(resp)
+        };
+
+        let resp = filter(serde_json::json!({ "include": ["(?i)gadionews"] }))?;
+        assert_eq!(titles(&resp), vec!["《死亡空间》回来了！GadioNews07.24"]);
+
+        let resp = filter(serde_json::json!({ "exclude": ["试听", "桌游"] }))?;
+        assert_eq!(titles(&resp), vec!["《死亡空间》回来了！GadioNews07.24"]);
+
+        //durations are 2830, 3474 and 4041
+        let resp = filter(serde_json::json!({ "min_duration": 3000, "max_duration": 4000 }))?;
+        assert_eq!(titles(&resp), vec!["用这几款桌游来体验穿梭在“生死之间”"]);
+
+        let resp = filter(serde_json::json!({ "categories": ["1"] }))?;
+        assert!(resp.data.is_empty());
+
+        let bad: Filter = serde_json::from_value(serde_json::json!({ "include": ["("] }))?;
+        assert!(bad.compile().is_err());
+        Ok(())
+    }
+}
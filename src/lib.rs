@@ -1,21 +1,157 @@
+pub mod config;
+mod error;
+mod filter;
+pub mod manifest;
 mod model;
 mod request;
 mod se;
+mod sign;
+pub mod store;
+pub mod validate;
+pub use error::Error;
+pub use filter::Filter;
 pub use model::Channel;
+pub use request::fetch;
 pub use request::req::Param;
-pub use service::get;
+pub use se::Format;
+pub use service::{get, get_with, publish, publish_with, stream, stream_with, Outcome};
 
 mod service {
-    use crate::model::Channel;
+    use crate::error::Error;
+    use crate::model::{api, Channel, Episode};
+    use crate::request::fetch::{Fetcher, Http};
     use crate::request::req;
-    use crate::se::{itune, Serializer};
-    use std::error::Error;
+    use crate::se::content_hash;
+    use crate::store::FeedStore;
+    use crate::validate;
+    use serde::Serialize;
+    use std::fmt;
+    use std::io::Write;
+    use std::sync::Arc;
 
-    pub async fn get(param: req::Param, ch_info: Channel) -> Result<String, Box<dyn Error>> {
-        let fetch_client = req::Client {};
-        let resp = fetch_client.fetch(param)?;
-        let serializer = itune::Client::default();
+    //what `publish` did with the store
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Outcome {
+        Published,
+        //rendered feed matched the stored one, nothing was written
+        Unchanged,
+    }
+
+    impl fmt::Display for Outcome {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Outcome::Published => write!(f, "published"),
+                Outcome::Unchanged => write!(f, "unchanged"),
+            }
+        }
+    }
+
+    pub async fn get(param: req::Param, ch_info: Channel) -> Result<String, Error> {
+        get_with(Http::default(), param, ch_info).await
+    }
+
+    pub async fn get_with<F: Fetcher>(
+        fetcher: F,
+        param: req::Param,
+        ch_info: Channel,
+    ) -> Result<String, Error> {
+        validate::check(&ch_info)?;
+        let serializer = param.format.serializer();
+        let fetch_client = req::Client::new(fetcher);
+        let resp = fetch_client.fetch(param).await?;
         let xml_str = serializer.to_xml(&ch_info, &resp)?;
         Ok(xml_str)
     }
+
+    //like `get`, the feed goes straight into `out` instead of a String
+    pub async fn stream<W: Write>(
+        param: req::Param,
+        ch_info: Channel,
+        out: &mut W,
+    ) -> Result<(), Error> {
+        stream_with(Http::default(), param, ch_info, out).await
+    }
+
+    pub async fn stream_with<F: Fetcher, W: Write>(
+        fetcher: F,
+        param: req::Param,
+        ch_info: Channel,
+        out: &mut W,
+    ) -> Result<(), Error> {
+        validate::check(&ch_info)?;
+        let serializer = param.format.serializer();
+        let fetch_client = req::Client::new(fetcher);
+        let resp = fetch_client.fetch(param).await?;
+        serializer.write_response(&ch_info, &resp, out)?;
+        out.flush().map_err(Error::storage)
+    }
+
+    //merge the fetched episodes into the stored feed and write it back
+    pub async fn publish(
+        param: req::Param,
+        ch_info: Channel,
+        store: Arc<dyn FeedStore>,
+    ) -> Result<Outcome, Error> {
+        publish_with(Http::default(), param, ch_info, store).await
+    }
+
+    pub async fn publish_with<F: Fetcher>(
+        fetcher: F,
+        param: req::Param,
+        ch_info: Channel,
+        store: Arc<dyn FeedStore>,
+    ) -> Result<Outcome, Error> {
+        let fetch_client = req::Client::new(fetcher);
+        let resp = fetch_client.fetch(param.clone()).await?;
+        store_feed(&resp, &param, &ch_info, store).await
+    }
+
+    //the backends block on their http clients, keep them off the async workers
+    async fn blocking<T, F>(store: &Arc<dyn FeedStore>, call: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn FeedStore) -> Result<T, Error> + Send + 'static,
+    {
+        let store = store.clone();
+        tokio::task::spawn_blocking(move || call(store.as_ref()))
+            .await
+            .map_err(Error::storage)?
+    }
+
+    //the output half of `publish`, for a response fetched already
+    pub(crate) async fn store_feed(
+        resp: &api::Response,
+        param: &req::Param,
+        ch_info: &Channel,
+        store: Arc<dyn FeedStore>,
+    ) -> Result<Outcome, Error> {
+        validate::check(ch_info)?;
+        let max_items = param.max_items.map(|n| n as usize);
+        let serializer = param.format.serializer();
+        let fetched = Episode::from_response(resp, ch_info)?;
+
+        //a stored feed that does not parse stops the publish, writing over it would lose its history
+        let published = match blocking(&store, |store| store.get()).await? {
+            Some(body) => {
+                let doc = String::from_utf8(body).map_err(Error::decode)?;
+                serializer.parse(&doc).map_err(|e| match e {
+                    Error::Decode(_) => e,
+                    e => Error::decode(e),
+                })?
+            }
+            None => vec![],
+        };
+
+        let episodes = Episode::merge(fetched, published, max_items);
+        let mut body = vec![];
+        serializer.write(ch_info, &episodes, &mut body)?;
+        let hash = store.hash_of(&content_hash(&body));
+        if blocking(&store, |store| store.hash()).await?.as_deref() == Some(hash.as_str()) {
+            return Ok(Outcome::Unchanged);
+        }
+        let content_type = param.format.content_type();
+        blocking(&store, move |store| store.put(body, &hash, content_type)).await?;
+        Ok(Outcome::Published)
+    }
 }
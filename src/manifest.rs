@@ -0,0 +1,156 @@
+use crate::config::{validate, Defaults};
+use crate::error::Error;
+use crate::model::{api, Channel};
+use crate::request::fetch::{Fetcher, Http};
+use crate::request::req::{Client, Param};
+use crate::service::{store_feed, Outcome};
+use crate::store::{self, StorageParam, Sts};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+//one feed of an event, the single feed event has no name
+#[derive(Deserialize)]
+pub struct Feed {
+    #[serde(default)]
+    pub name: String,
+    pub channel: Channel,
+    pub param: Param,
+    pub storage_param: StorageParam,
+}
+
+//a function event, either one feed or a manifest `{"feeds": [..]}`
+pub enum Event {
+    One(Box<Feed>),
+    Many(Vec<Feed>),
+}
+
+impl Event {
+    pub fn from_value(value: serde_json::Value) -> Result<Self, Error> {
+        #[derive(Deserialize)]
+        struct Manifest {
+            feeds: Vec<Feed>,
+        }
+
+        let event = match value.get("feeds").and_then(Value::as_array) {
+            Some(feeds) => {
+                for (i, feed) in feeds.iter().enumerate() {
+                    validate(feed, &format!("feeds[{}].", i))?;
+                }
+                serde_json::from_value(value).map(|Manifest { feeds }| Event::Many(feeds))
+            }
+            None => {
+                validate(&value, "")?;
+                serde_json::from_value(value).map(|feed| Event::One(Box::new(feed)))
+            }
+        };
+        event.map_err(|e| Error::Config(e.into()))
+    }
+
+    //the event over `Defaults::load`
+    pub fn with_defaults(value: Value) -> Result<Self, Error> {
+        Event::from_value(Defaults::load()?.apply(value))
+    }
+
+    pub fn from_slice(data: &[u8]) -> Result<Self, Error> {
+        let value = serde_json::from_slice(data).map_err(|e| Error::Config(e.into()))?;
+        Event::with_defaults(value)
+    }
+}
+
+//result of one feed of a manifest
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Outcome>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    //http status of the error, 200 when published
+    #[serde(skip)]
+    pub status: u16,
+}
+
+impl Report {
+    fn new(name: String, result: Result<Outcome, Error>) -> Self {
+        match result {
+            Ok(outcome) => Report {
+                name,
+                outcome: Some(outcome),
+                error: None,
+                status: 200,
+            },
+            Err(e) => Report::failed(name, e.status(), e.to_string()),
+        }
+    }
+
+    fn failed(name: String, status: u16, error: String) -> Self {
+        log::error!("feed {}: {}", name, error);
+        Report {
+            name,
+            outcome: None,
+            error: Some(error),
+            status,
+        }
+    }
+}
+
+//fetch errors are not Clone, every feed of the key reports the status and message
+type Fetched = Result<api::Response, (u16, String)>;
+
+pub async fn publish_all(feeds: Vec<Feed>, sts: Option<Sts>) -> Vec<Report> {
+    publish_all_with(Http::default(), feeds, sts).await
+}
+
+//feeds with the same `Param::fetch_key` share one fetch, a failing feed does not stop the others
+pub async fn publish_all_with<F: Fetcher>(
+    fetcher: F,
+    feeds: Vec<Feed>,
+    sts: Option<Sts>,
+) -> Vec<Report> {
+    let client = Client::new(fetcher);
+    let mut fetched: Vec<(Param, Fetched)> = vec![];
+    let mut reports = Vec::with_capacity(feeds.len());
+
+    for feed in feeds {
+        let key = feed.param.fetch_key();
+        let cached = match fetched.iter().position(|(k, _)| *k == key) {
+            Some(i) => i,
+            None => {
+                let resp = client
+                    .fetch(key.clone())
+                    .await
+                    .map_err(|e| (e.status(), e.to_string()));
+                fetched.push((key, resp));
+                fetched.len() - 1
+            }
+        };
+        let report = match &fetched[cached].1 {
+            Ok(resp) => {
+                let result =
+                    publish_one(resp, feed.param, feed.channel, feed.storage_param, &sts).await;
+                Report::new(feed.name, result)
+            }
+            Err((status, msg)) => Report::failed(feed.name, *status, msg.clone()),
+        };
+        reports.push(report);
+    }
+    reports
+}
+
+async fn publish_one(
+    resp: &api::Response,
+    param: Param,
+    channel: Channel,
+    storage_param: StorageParam,
+    sts: &Option<Sts>,
+) -> Result<Outcome, Error> {
+    let mut resp = resp.clone();
+    if let Some(filter) = &param.filter {
+        filter.compile()?.retain(&mut resp);
+    }
+    if let Some(n) = param.limit {
+        resp.truncate_episodes(n as usize);
+    }
+    let store = store::open(storage_param, sts.clone())?;
+    store_feed(&resp, &param, &channel, store).await
+}
@@ -1,20 +1,44 @@
+use crate::error::Error;
+use chrono::{DateTime, FixedOffset};
+use std::cmp::Reverse;
+
 pub mod api {
+    use chrono::{DateTime, FixedOffset, ParseError};
     use serde::Deserialize;
 
-    #[derive(Deserialize, Debug)]
+    #[derive(Deserialize, Debug, Clone)]
     pub struct Response {
         pub data: Vec<Radio>,
-        pub included: Vec<inc::Media>,
+        #[serde(deserialize_with = "inc::lenient")]
+        pub included: Vec<inc::Included>,
+        #[serde(default)]
+        pub meta: Option<Meta>,
+        #[serde(default)]
+        pub links: Option<Links>,
+    }
+
+    #[derive(Deserialize, Debug, Clone)]
+    #[serde(rename_all(deserialize = "kebab-case"))]
+    pub struct Meta {
+        pub record_count: u32,
     }
 
-    #[derive(Deserialize, Debug)]
+    #[derive(Deserialize, Debug, Clone)]
+    pub struct Links {
+        #[serde(default)]
+        pub next: Option<String>,
+    }
+
+    #[derive(Deserialize, Debug, Clone)]
     pub struct Radio {
         //link: https://www.gcores.com/radios/{id}
         pub id: String,
         pub attributes: Attribute,
+        #[serde(default)]
+        pub relationships: Relationships,
     }
 
-    #[derive(Deserialize, Debug)]
+    #[derive(Deserialize, Debug, Clone)]
     #[serde(rename_all(deserialize = "kebab-case"))]
     pub struct Attribute {
         pub title: String,
@@ -23,35 +47,634 @@ pub mod api {
         pub published_at: String,
     }
 
+    #[derive(Deserialize, Debug, Clone, Default)]
+    pub struct Relationships {
+        #[serde(default)]
+        pub media: Relationship,
+        //hosts and guests of the radio
+        #[serde(default)]
+        pub djs: Relationship,
+        #[serde(default)]
+        pub category: Relationship,
+        #[serde(default)]
+        pub tags: Relationship,
+    }
+
+    // json:api resource linkage, `data` is null, a single identifier or a list
+    #[derive(Deserialize, Debug, Clone, Default)]
+    pub struct Relationship {
+        #[serde(default)]
+        pub data: Option<Linkage>,
+    }
+
+    #[derive(Deserialize, Debug, Clone)]
+    #[serde(untagged)]
+    pub enum Linkage {
+        One(Identifier),
+        Many(Vec<Identifier>),
+    }
+
+    #[derive(Deserialize, Debug, Clone, PartialEq)]
+    pub struct Identifier {
+        #[serde(rename = "type")]
+        pub kind: String,
+        pub id: String,
+    }
+
+    impl Radio {
+        pub fn published(&self) -> Result<DateTime<FixedOffset>, ParseError> {
+            DateTime::parse_from_rfc3339(&self.attributes.published_at)
+        }
+    }
+
+    impl Relationship {
+        pub fn identifiers(&self) -> &[Identifier] {
+            match &self.data {
+                Some(Linkage::One(id)) => std::slice::from_ref(id),
+                Some(Linkage::Many(ids)) => ids,
+                None => &[],
+            }
+        }
+    }
+
+    impl Response {
+        // append a following page, resources already included are not repeated
+        pub fn merge(&mut self, page: Response) {
+            self.data.extend(page.data);
+            for res in page.included {
+                let known = match res.identifier() {
+                    Some(ident) => self.included.iter().any(|r| r.is(&ident)),
+                    None => true,
+                };
+                if !known {
+                    self.included.push(res);
+                }
+            }
+            self.meta = page.meta;
+            self.links = page.links;
+        }
+
+        // included resources of a relationship in linkage order
+        fn resolve(&self, rel: &Relationship) -> Vec<&inc::Included> {
+            rel.identifiers()
+                .iter()
+                .filter_map(|ident| self.included.iter().find(|r| r.is(ident)))
+                .collect()
+        }
+
+        // media linked to the radio, the first one found in `included`
+        pub fn media_of(&self, radio: &Radio) -> Option<&inc::Media> {
+            self.resolve(&radio.relationships.media)
+                .into_iter()
+                .find_map(|res| match res {
+                    inc::Included::Media(media) => Some(media),
+                    _ => None,
+                })
+        }
+
+        // names of the radio category followed by its tags
+        pub fn categories_of(&self, radio: &Radio) -> Vec<&str> {
+            let rels = &radio.relationships;
+            self.resolve(&rels.category)
+                .into_iter()
+                .chain(self.resolve(&rels.tags))
+                .filter_map(|res| match res {
+                    inc::Included::Category(term) | inc::Included::Tag(term) => {
+                        Some(term.attributes.name.as_str())
+                    }
+                    _ => None,
+                })
+                .collect()
+        }
+
+        // users on the radio in linkage order, those missing from `included` are left out
+        pub fn djs_of(&self, radio: &Radio) -> Vec<&inc::User> {
+            self.resolve(&radio.relationships.djs)
+                .into_iter()
+                .filter_map(|res| match res {
+                    inc::Included::User(user) => Some(user),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        // keep the radios up to the `n`th one with media, those without take no place
+        pub fn truncate_episodes(&mut self, n: usize) {
+            let end = self
+                .data
+                .iter()
+                .enumerate()
+                .filter(|(_, radio)| self.media_of(radio).is_some())
+                .nth(n)
+                .map(|(i, _)| i);
+            if let Some(end) = end {
+                self.data.truncate(end);
+            }
+        }
+
+        // radios paired with their audio, radios without one are logged and skipped
+        pub fn episodes(&self) -> Vec<(&Radio, &inc::Media)> {
+            self.data
+                .iter()
+                .filter_map(|radio| match self.media_of(radio) {
+                    Some(media) => Some((radio, media)),
+                    None => {
+                        log::warn!("radio {} has no media, skipped", radio.id);
+                        None
+                    }
+                })
+                .collect()
+        }
+    }
+
     pub mod inc {
-        use serde::Deserialize;
-        #[derive(Deserialize, Debug)]
+        use super::Identifier;
+        use serde::{Deserialize, Deserializer};
+
+        const MEDIAS: &str = "medias";
+        const USERS: &str = "users";
+        const CATEGORIES: &str = "categories";
+        const TAGS: &str = "tags";
+
+        //resources of `included`, told apart by their json:api type
+        #[derive(Deserialize, Debug, Clone)]
+        #[serde(tag = "type")]
+        pub enum Included {
+            #[serde(rename = "medias")]
+            Media(Media),
+            #[serde(rename = "users")]
+            User(User),
+            #[serde(rename = "categories")]
+            Category(Term),
+            #[serde(rename = "tags")]
+            Tag(Term),
+            //types the feed does not use
+            #[serde(other)]
+            Other,
+        }
+
+        //a resource that does not read, like a category without a name, is `Other` too
+        pub fn lenient<'de, D: Deserializer<'de>>(de: D) -> Result<Vec<Included>, D::Error> {
+            let resources = Vec::<serde_json::Value>::deserialize(de)?;
+            Ok(resources
+                .into_iter()
+                .map(|res| Included::deserialize(res).unwrap_or(Included::Other))
+                .collect())
+        }
+
+        impl Included {
+            pub fn identifier(&self) -> Option<Identifier> {
+                let (kind, id) = match self {
+                    Included::Media(media) => (MEDIAS, &media.id),
+                    Included::User(user) => (USERS, &user.id),
+                    Included::Category(term) => (CATEGORIES, &term.id),
+                    Included::Tag(term) => (TAGS, &term.id),
+                    Included::Other => return None,
+                };
+                Some(Identifier {
+                    kind: kind.to_string(),
+                    id: id.clone(),
+                })
+            }
+
+            pub fn is(&self, ident: &Identifier) -> bool {
+                self.identifier().as_ref() == Some(ident)
+            }
+        }
+
+        #[derive(Deserialize, Debug, Clone)]
         pub struct Media {
             pub id: String,
             pub attributes: Attribute,
         }
 
-        #[derive(Deserialize, Debug)]
+        #[derive(Deserialize, Debug, Clone)]
         pub struct Attribute {
             pub audio: String,
             pub duration: u16,
         }
+
+        #[derive(Deserialize, Debug, Clone)]
+        pub struct User {
+            pub id: String,
+            pub attributes: UserAttribute,
+        }
+
+        //a category or a tag
+        #[derive(Deserialize, Debug, Clone)]
+        pub struct Term {
+            pub id: String,
+            pub attributes: TermAttribute,
+        }
+
+        #[derive(Deserialize, Debug, Clone)]
+        pub struct TermAttribute {
+            pub name: String,
+        }
+
+        #[derive(Deserialize, Debug, Clone)]
+        pub struct UserAttribute {
+            pub nickname: String,
+            //avatar, relative to the image base url
+            #[serde(default)]
+            pub thumb: Option<String>,
+        }
+    }
+
+    //gapi resources for the tests that build responses by hand
+    #[cfg(test)]
+    pub(crate) mod fixture {
+        use serde_json::json;
+
+        pub(crate) fn radio(id: &str, media: serde_json::Value) -> serde_json::Value {
+            json!({
+                "id": id,
+                "type": "radios",
+                "attributes": {
+                    "title": format!("radio {}", id),
+                    "desc": "",
+                    "thumb": "",
+                    "published-at": "2021-07-27T22:00:00.000+08:00"
+                },
+                "relationships": { "media": { "data": media } }
+            })
+        }
+
+        pub(crate) fn user(id: &str) -> serde_json::Value {
+            json!({
+                "id": id,
+                "type": "users",
+                "attributes": { "nickname": format!("user {}", id), "thumb": null }
+            })
+        }
+
+        pub(crate) fn media(id: &str) -> serde_json::Value {
+            json!({
+                "id": id,
+                "type": "medias",
+                "attributes": { "audio": format!("{}.mp3", id), "duration": 60 }
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::fixture::{media, radio, user};
+        use super::Response;
+        use serde_json::json;
+
+        #[test]
+        fn join_by_linkage() -> Result<(), serde_json::Error> {
+            let resp: Response = serde_json::from_value(json!({
+                "data": [
+                    radio("1", json!({"type": "medias", "id": "b"})),
+                    radio("2", json!(null)),
+                    radio("3", json!([{"type": "medias", "id": "x"}, {"type": "medias", "id": "a"}])),
+                    radio("4", json!({"type": "videos", "id": "a"})),
+                ],
+                "included": [media("a"), media("b")]
+            }))?;
+            let pairs: Vec<(&str, &str)> = resp
+                .episodes()
+                .into_iter()
+                .map(|(r, m)| (r.id.as_str(), m.attributes.audio.as_str()))
+                .collect();
+            assert_eq!(pairs, vec![("1", "b.mp3"), ("3", "a.mp3")]);
+            Ok(())
+        }
+
+        #[test]
+        fn merge_pages() -> Result<(), serde_json::Error> {
+            let mut resp: Response = serde_json::from_value(json!({
+                "data": [radio("1", json!({"type": "medias", "id": "a"}))],
+                "included": [media("a")],
+                "meta": { "record-count": 2 }
+            }))?;
+            let page: Response = serde_json::from_value(json!({
+                "data": [radio("2", json!({"type": "medias", "id": "a"}))],
+                "included": [media("a")],
+                "links": { "next": null }
+            }))?;
+            resp.merge(page);
+            assert_eq!(resp.data.len(), 2);
+            assert_eq!(resp.included.len(), 1);
+            assert_eq!(resp.episodes().len(), 2);
+            Ok(())
+        }
+
+        #[test]
+        fn join_users() -> Result<(), serde_json::Error> {
+            let mut with_djs = radio("1", json!({"type": "medias", "id": "a"}));
+            with_djs["relationships"]["djs"] = json!({"data": [
+                {"type": "users", "id": "9"},
+                {"type": "users", "id": "404"},
+                {"type": "users", "id": "7"}
+            ]});
+            let resp: Response = serde_json::from_value(json!({
+                "data": [with_djs, radio("2", json!({"type": "medias", "id": "a"}))],
+                "included": [
+                    media("a"),
+                    user("7"),
+                    user("9"),
+                    {"type": "categories", "id": "1", "attributes": {}},
+                    {"type": "topics", "id": "2", "attributes": {}}
+                ]
+            }))?;
+            let names: Vec<&str> = resp
+                .djs_of(&resp.data[0])
+                .iter()
+                .map(|u| u.attributes.nickname.as_str())
+                .collect();
+            assert_eq!(names, vec!["user 9", "user 7"]);
+            assert!(resp.djs_of(&resp.data[1]).is_empty());
+            Ok(())
+        }
+
+        #[test]
+        fn join_terms() -> Result<(), serde_json::Error> {
+            let mut tagged = radio("1", json!({"type": "medias", "id": "a"}));
+            tagged["relationships"]["category"] =
+                json!({"data": {"type": "categories", "id": "3"}});
+            tagged["relationships"]["tags"] = json!({"data": [
+                {"type": "tags", "id": "5"},
+                {"type": "tags", "id": "3"}
+            ]});
+            let term = |kind: &str, id: &str, name: &str| json!({"type": kind, "id": id, "attributes": {"name": name}});
+            let resp: Response = serde_json::from_value(json!({
+                "data": [tagged],
+                "included": [
+                    media("a"),
+                    term("tags", "3", "主机"),
+                    term("tags", "5", "桌游"),
+                    term("categories", "3", "游戏新闻")
+                ]
+            }))?;
+            assert_eq!(
+                resp.categories_of(&resp.data[0]),
+                vec!["游戏新闻", "桌游", "主机"]
+            );
+            Ok(())
+        }
+    }
+}
+
+//one feed entry, built from the api or read back from a published feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Episode {
+    pub guid: String,
+    pub title: String,
+    pub description: String,
+    pub link: String,
+    pub audio_url: String,
+    //bytes, 0 when unknown
+    pub length: u64,
+    //seconds
+    pub duration: u32,
+    pub image: String,
+    pub published: DateTime<FixedOffset>,
+    //podcast:transcript and podcast:chapters urls
+    pub transcript: Option<String>,
+    pub chapters: Option<String>,
+    //djs of the radio
+    pub persons: Vec<Person>,
+    //category and tag names
+    pub categories: Vec<String>,
+}
+
+impl Episode {
+    pub fn from_api(
+        radio: &api::Radio,
+        media: &api::inc::Media,
+        djs: &[&api::inc::User],
+        categories: &[&str],
+        ch: &Channel,
+    ) -> Result<Self, Error> {
+        let audio_url = format!("{}{}", ch.media_base_url, media.attributes.audio);
+        //a thumb without a base url is a bare file name, the channel artwork stands in
+        let image = if radio.attributes.thumb.is_empty() || ch.image_base_url.is_empty() {
+            ch.image.clone()
+        } else {
+            format!("{}{}", ch.image_base_url, radio.attributes.thumb)
+        };
+        let per_radio = |template: &str| template.replace("{id}", &radio.id);
+        Ok(Episode {
+            guid: audio_url.clone(),
+            title: radio.attributes.title.clone(),
+            description: radio.attributes.desc.clone(),
+            link: format!("{}{}", ch.web_base_url, radio.id),
+            audio_url,
+            //file size is not provided by the api
+            length: 0,
+            duration: media.attributes.duration as u32,
+            image,
+            published: radio.published()?,
+            transcript: ch
+                .transcript
+                .as_ref()
+                .filter(|t| t.ids.contains(&radio.id))
+                .map(|t| per_radio(&t.url)),
+            chapters: ch
+                .chapters
+                .as_ref()
+                .filter(|c| c.ids.contains(&radio.id))
+                .map(|c| per_radio(&c.url)),
+            persons: djs
+                .iter()
+                .map(|user| Person {
+                    name: user.attributes.nickname.clone(),
+                    role: Person::host(),
+                    href: None,
+                    img: user
+                        .attributes
+                        .thumb
+                        .as_ref()
+                        .filter(|t| !t.is_empty() && !ch.image_base_url.is_empty())
+                        .map(|t| format!("{}{}", ch.image_base_url, t)),
+                })
+                .collect(),
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+        })
+    }
+
+    //episodes of the response in api order
+    pub fn from_response(resp: &api::Response, ch: &Channel) -> Result<Vec<Self>, Error> {
+        resp.episodes()
+            .into_iter()
+            .map(|(radio, media)| {
+                Episode::from_api(
+                    radio,
+                    media,
+                    &resp.djs_of(radio),
+                    &resp.categories_of(radio),
+                    ch,
+                )
+            })
+            .collect()
+    }
+
+    //fetched episodes replace published ones with the same guid, newest first
+    pub fn merge(fetched: Vec<Self>, published: Vec<Self>, max: Option<usize>) -> Vec<Self> {
+        let mut merged = fetched;
+        for ep in published {
+            if !merged.iter().any(|e| e.guid == ep.guid) {
+                merged.push(ep);
+            }
+        }
+        merged.sort_by_key(|e| Reverse(e.published));
+        if let Some(n) = max {
+            merged.truncate(n);
+        }
+        merged
     }
 }
 
+//fields left out of the event come from the config defaults, see `config::validate`
 #[derive(Default, serde::Deserialize)]
+#[serde(default)]
 pub struct Channel {
     pub title: String,
     pub author: String,
     pub description: String,
     pub image: String,
     pub language: String,
+    //nested itunes categories
+    #[serde(default)]
+    pub categories: Vec<ItunesCategory>,
+    //legacy pair of a category and its subcategory, used when `categories` is empty
+    #[serde(default)]
     pub category1: String,
+    #[serde(default)]
     pub category2: String,
     pub link: String,
     pub owner_name: String,
     pub owner_email: String,
     pub media_base_url: String,
     pub web_base_url: String,
-    pub explicit: String,
+    //episode artwork is {image_base_url}{thumb}
+    #[serde(default)]
+    pub image_base_url: String,
+    //"true"/"false" and the older "yes"/"no"/"clean" strings are accepted too
+    #[serde(deserialize_with = "explicit")]
+    pub explicit: bool,
+    //podcast namespace, https://podcastindex.org/namespace/1.0
+    #[serde(default)]
+    pub podcast_guid: Option<String>,
+    #[serde(default)]
+    pub locked: Option<bool>,
+    #[serde(default)]
+    pub funding: Option<Funding>,
+    #[serde(default)]
+    pub persons: Vec<Person>,
+    //episode urls, {id} is replaced with the radio id
+    #[serde(default)]
+    pub transcript: Option<Transcript>,
+    #[serde(default)]
+    pub chapters: Option<Chapters>,
+}
+
+fn explicit<'de, D: serde::Deserializer<'de>>(de: D) -> Result<bool, D::Error> {
+    use serde::de::{Deserialize, Error};
+
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Bool(bool),
+        Text(String),
+    }
+
+    match Flag::deserialize(de)? {
+        Flag::Bool(b) => Ok(b),
+        Flag::Text(s) => match s.to_lowercase().as_str() {
+            "true" | "yes" | "explicit" => Ok(true),
+            "false" | "no" | "clean" | "" => Ok(false),
+            other => Err(D::Error::custom(format!(
+                "explicit must be true or false, got {}",
+                other
+            ))),
+        },
+    }
+}
+
+impl Channel {
+    pub fn itunes_categories(&self) -> Vec<ItunesCategory> {
+        if !self.categories.is_empty() || self.category1.is_empty() {
+            return self.categories.clone();
+        }
+        let subcategories = match self.category2.as_str() {
+            "" => vec![],
+            sub => vec![ItunesCategory {
+                text: sub.to_string(),
+                subcategories: vec![],
+            }],
+        };
+        vec![ItunesCategory {
+            text: self.category1.clone(),
+            subcategories,
+        }]
+    }
+
+    //whether any podcast namespace tag is configured
+    pub fn has_podcast(&self) -> bool {
+        self.podcast_guid.is_some()
+            || self.locked.is_some()
+            || self.funding.is_some()
+            || !self.persons.is_empty()
+            || self.transcript.is_some()
+            || self.chapters.is_some()
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct ItunesCategory {
+    pub text: String,
+    #[serde(default)]
+    pub subcategories: Vec<ItunesCategory>,
+}
+
+#[derive(Default, Debug, Clone, serde::Deserialize)]
+pub struct Funding {
+    pub url: String,
+    pub text: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Person {
+    pub name: String,
+    #[serde(default = "Person::host")]
+    pub role: String,
+    #[serde(default)]
+    pub href: Option<String>,
+    #[serde(default)]
+    pub img: Option<String>,
+}
+
+impl Person {
+    fn host() -> String {
+        "host".to_string()
+    }
+}
+
+#[derive(Default, Debug, Clone, serde::Deserialize)]
+pub struct Transcript {
+    pub url: String,
+    //mime type of the transcript
+    #[serde(rename = "type", default = "Transcript::vtt")]
+    pub kind: String,
+    //radios that have a transcript
+    #[serde(default)]
+    pub ids: Vec<String>,
+}
+
+impl Transcript {
+    fn vtt() -> String {
+        "text/vtt".to_string()
+    }
+}
+
+#[derive(Default, Debug, Clone, serde::Deserialize)]
+pub struct Chapters {
+    pub url: String,
+    //radios that have chapters
+    #[serde(default)]
+    pub ids: Vec<String>,
 }
@@ -1,13 +1,15 @@
 // example of request
-// https://www.gcores.com/gapi/v1/radios?page[limit]=3&filter[list-all]=0&page[offset]=12&sort=-published-at&fields[radios]=title,desc,thumb,published-at&include=media
+// https://www.gcores.com/gapi/v1/radios?page[limit]=3&filter[list-all]=0&page[offset]=12&sort=-published-at&fields[radios]=title,desc,thumb,published-at,media,djs,category,tags&include=media,djs,category,tags
 
 mod url {
 
-    pub fn concat_url(url: String, start: u16, size: u16) -> Option<String> {
+    pub fn concat_url(url: String, start: u32, size: u16) -> Option<String> {
         let p_at: &str = "published-at";
         let fields_bs: &str = "title,desc,thumb";
+        //sparse fieldsets also drop relationships that are not listed
+        let relations: &str = "media,djs,category,tags";
         let val_sort = format!("-{}", p_at);
-        let val_fields = format!("{},{}", fields_bs, p_at);
+        let val_fields = format!("{},{},{}", fields_bs, p_at, relations);
 
         let size_str = &(size.to_string());
         let start_str = &(start.to_string());
@@ -19,7 +21,8 @@ mod url {
             ("filter[list-all]", "0"),
             ("sort", &val_sort),
             ("fields[radios]", &val_fields),
-            ("include", "media"),
+            //audio, the users hosting the radio and its categorization
+            ("include", "media,djs,category,tags"),
         ];
 
         params
@@ -29,47 +32,311 @@ mod url {
             .map(|s| format!("{}?{}", url, s))
     }
 
+    //keep the query of a pagination link but send it to our own base url
+    pub fn rebase(url: &str, link: &str) -> String {
+        match link.split_once('?') {
+            Some((_, query)) => format!("{}?{}", url, query),
+            None => link.to_owned(),
+        }
+    }
+
     #[cfg(test)]
     mod tests {
-        use super::concat_url;
+        use super::{concat_url, rebase};
         use simple_error::SimpleError;
 
         #[test]
         fn concat() -> Result<(), SimpleError> {
             let url1 = "https://www.gcores.com/gapi/v1/radios";
-            let start = 5u16;
+            let start = 5u32;
             let size = 3u16;
             let url2 =
                 concat_url(url1.to_string(), start, size).ok_or(SimpleError::new("url error"))?;
-            let expected = format!("{}?{}", url1, "page[limit]=3&page[offset]=5&sort=-published-at&fields[radios]=title,desc,thumb,published-at&include=media");
+            let expected = format!("{}?{}", url1, "page[limit]=3&page[offset]=5&filter[list-all]=0&sort=-published-at&fields[radios]=title,desc,thumb,published-at,media,djs,category,tags&include=media,djs,category,tags");
             assert_eq!(url2, expected);
             Ok(())
         }
+
+        #[test]
+        fn rebase_link() {
+            let next = "https://www.gcores.com/gapi/v1/radios?page[limit]=3&page[offset]=3";
+            assert_eq!(
+                rebase("http://127.0.0.1:8080/radios", next),
+                "http://127.0.0.1:8080/radios?page[limit]=3&page[offset]=3"
+            );
+        }
+    }
+}
+
+pub mod fetch {
+    use crate::error::Error;
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+
+    //transport used by req::Client to download api pages
+    #[async_trait]
+    pub trait Fetcher: Send + Sync {
+        async fn get(&self, url: &str) -> Result<String, Error>;
+    }
+
+    //a shared fetcher, the caller keeps a handle to inspect it
+    #[async_trait]
+    impl<F: Fetcher> Fetcher for Arc<F> {
+        async fn get(&self, url: &str) -> Result<String, Error> {
+            (**self).get(url).await
+        }
+    }
+
+    #[derive(Default)]
+    pub struct Http {
+        client: reqwest::Client,
+    }
+
+    #[async_trait]
+    impl Fetcher for Http {
+        async fn get(&self, url: &str) -> Result<String, Error> {
+            let body = self
+                .client
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+            Ok(body)
+        }
+    }
+
+    //serves fixture bodies without network, records requested urls
+    pub struct Memory {
+        routes: Vec<(String, String)>,
+        fallback: Option<String>,
+        requested: Mutex<Vec<String>>,
+    }
+
+    impl Memory {
+        //serve `body` for any url
+        pub fn new(body: impl Into<String>) -> Self {
+            Memory {
+                routes: vec![],
+                fallback: Some(body.into()),
+                requested: Mutex::new(vec![]),
+            }
+        }
+
+        //serve `body` for urls containing `pattern`, checked in insertion order
+        pub fn route(mut self, pattern: impl Into<String>, body: impl Into<String>) -> Self {
+            self.routes.push((pattern.into(), body.into()));
+            self
+        }
+
+        pub fn requested(&self) -> Vec<String> {
+            self.requested.lock().unwrap().clone()
+        }
+    }
+
+    impl Default for Memory {
+        fn default() -> Self {
+            Memory {
+                routes: vec![],
+                fallback: None,
+                requested: Mutex::new(vec![]),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Fetcher for Memory {
+        async fn get(&self, url: &str) -> Result<String, Error> {
+            self.requested.lock().unwrap().push(url.to_owned());
+            self.routes
+                .iter()
+                .find(|(pattern, _)| url.contains(pattern.as_str()))
+                .map(|(_, body)| body)
+                .or_else(|| self.fallback.as_ref())
+                .cloned()
+                .ok_or_else(|| Error::Http(format!("no fixture for {}", url).into()))
+        }
     }
 }
 
 pub mod req {
-    use super::url::concat_url;
+    use super::fetch::{Fetcher, Http};
+    use super::url::{concat_url, rebase};
+    use crate::error::Error;
+    use crate::filter::Filter;
     use crate::model::api;
-    use simple_error::SimpleError;
-    use std::error::Error;
+    use crate::se::Format;
+    use chrono::DateTime;
+    use std::time::Duration;
+
+    //milliseconds between two page requests
+    const DEFAULT_DELAY: u64 = 500;
 
-    pub struct Client {}
+    pub struct Client<F> {
+        fetcher: F,
+    }
 
-    #[derive(Debug, Clone, serde::Deserialize)]
+    #[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+    #[serde(default)]
     pub struct Param {
         pub url: String,
-        pub start: u16,
+        pub start: u32,
+        //page size
         pub size: u16,
+        //follow pages until this many episodes are fetched, a single page when absent
+        #[serde(default)]
+        pub limit: Option<u32>,
+        //follow pages until episodes published before this rfc3339 time
+        #[serde(default)]
+        pub until: Option<String>,
+        //stop after this many page requests whatever the other limits say
+        #[serde(default)]
+        pub pages: Option<u32>,
+        //milliseconds to wait between page requests
+        #[serde(default)]
+        pub delay: Option<u64>,
+        //episodes kept in the published feed across runs, unlimited when absent
+        #[serde(default)]
+        pub max_items: Option<u32>,
+        //rss (default) or atom
+        #[serde(default)]
+        pub format: Format,
+        //only episodes passing the filter are kept, `limit` counts matches
+        #[serde(default)]
+        pub filter: Option<Filter>,
+    }
+
+    impl Param {
+        //what decides the upstream requests, feeds agreeing on it can share one fetch;
+        //a filter only matters there when `limit` counts its matches
+        pub fn fetch_key(&self) -> Param {
+            Param {
+                url: self.url.clone(),
+                start: self.start,
+                size: self.size,
+                limit: self.limit,
+                until: self.until.clone(),
+                pages: self.pages,
+                delay: self.delay,
+                filter: self.filter.clone().filter(|_| self.limit.is_some()),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl Default for Client<Http> {
+        fn default() -> Self {
+            Client::new(Http::default())
+        }
     }
 
-    impl Client {
-        pub fn fetch(&self, param: Param) -> Result<api::Response, Box<dyn Error>> {
-            let url1 = concat_url(param.url.clone(), param.start, param.size)
-                .ok_or_else(|| SimpleError::new("url error"))?;
+    impl<F: Fetcher> Client<F> {
+        pub fn new(fetcher: F) -> Self {
+            Client { fetcher }
+        }
+
+        pub async fn fetch(&self, param: Param) -> Result<api::Response, Error> {
+            let until = match &param.until {
+                Some(s) => Some(
+                    DateTime::parse_from_rfc3339(s)
+                        .map_err(|e| Error::config(format!("until {}: {}", s, e)))?,
+                ),
+                None => None,
+            };
+            let matcher = match &param.filter {
+                Some(filter) => Some(filter.compile()?),
+                None => None,
+            };
+            let paging = param.limit.is_some() || until.is_some() || param.pages.is_some();
+            let delay = Duration::from_millis(param.delay.unwrap_or(DEFAULT_DELAY));
+
+            let mut offset = param.start;
+            let mut url1 = concat_url(param.url.clone(), offset, param.size)
+                .ok_or_else(|| Error::config("url error"))?;
+            let mut resp: Option<api::Response> = None;
+            //radios of the fetched pages that end up as episodes
+            let mut emitted = 0usize;
+            let mut requests = 0u32;
+
+            loop {
+                let body = self.fetcher.get(&url1).await?;
+                requests += 1;
+                let page: api::Response = serde_json::from_str(&body)?;
+                let count = page.data.len() as u32;
+                offset += count;
 
-            let resp: api::Response = ureq::get(&url1).call()?.into_json()?;
+                let reached_until = match (until, page.data.last()) {
+                    (Some(t), Some(radio)) => radio.published()? < t,
+                    _ => false,
+                };
+                let exhausted = match &page.meta {
+                    Some(meta) => offset >= meta.record_count,
+                    None => false,
+                };
+                let next = page.links.as_ref().and_then(|l| l.next.clone());
 
+                let acc = match resp.take() {
+                    Some(mut acc) => {
+                        acc.merge(page);
+                        acc
+                    }
+                    None => page,
+                };
+                //only the new page is counted, its relationships resolve against all pages
+                emitted += acc.data[acc.data.len() - count as usize..]
+                    .iter()
+                    .filter(|r| acc.media_of(r).is_some())
+                    .filter(|r| match &matcher {
+                        Some(m) => m.matches(&acc, r),
+                        None => true,
+                    })
+                    .count();
+                let enough = match param.limit {
+                    Some(n) => emitted >= n as usize,
+                    None => false,
+                };
+                let last_page = match param.pages {
+                    Some(n) => requests >= n,
+                    None => false,
+                };
+                resp = Some(acc);
+
+                if !paging
+                    || count == 0
+                    || count < param.size as u32
+                    || enough
+                    || last_page
+                    || reached_until
+                    || exhausted
+                {
+                    break;
+                }
+
+                url1 = match next {
+                    Some(next) => rebase(&param.url, &next),
+                    None => concat_url(param.url.clone(), offset, param.size)
+                        .ok_or_else(|| Error::config("url error"))?,
+                };
+                tokio::time::sleep(delay).await;
+            }
+
+            let mut resp = resp.ok_or_else(|| Error::config("no page fetched"))?;
+            if let Some(t) = until {
+                let mut kept = Vec::with_capacity(resp.data.len());
+                for radio in resp.data {
+                    if radio.published()? >= t {
+                        kept.push(radio);
+                    }
+                }
+                resp.data = kept;
+            }
+            if let Some(m) = &matcher {
+                m.retain(&mut resp);
+            }
+            if let Some(n) = param.limit {
+                resp.truncate_episodes(n as usize);
+            }
             Ok(resp)
         }
     }
@@ -77,26 +344,160 @@ pub mod req {
     #[cfg(test)]
     mod tests {
         use super::*;
+        use crate::model::api::fixture::{media, radio};
+        use crate::request::fetch::Memory;
         use std::error::Error;
+        use std::fs;
+        use std::sync::Arc;
 
-        #[test]
-        fn get_json() -> Result<(), Box<dyn Error>> {
+        #[tokio::test]
+        async fn get_json() -> Result<(), Box<dyn Error>> {
             let url1 = "https://www.gcores.com/gapi/v1/radios";
             let param = Param {
                 url: url1.to_owned(),
-                start: 3u16,
+                start: 3u32,
                 size: 4u16,
+                ..Default::default()
             };
-            let cl = Client {};
-            let resp = cl.fetch(param)?;
-            for radio in resp.data {
-                println!("{}", radio.attributes.title);
-                println!("{}", radio.attributes.published_at);
-            }
-            for med in resp.included {
-                println!("{}", med.attributes.audio);
-                println!("duration: {}", med.attributes.duration);
-            }
+            let json: String = fs::read_to_string("api_response.json")?;
+            let fake = Arc::new(Memory::new(json));
+            let resp = Client::new(fake.clone()).fetch(param).await?;
+            assert_eq!(
+                fake.requested(),
+                vec![concat_url(url1.to_owned(), 3, 4).unwrap()]
+            );
+            let titles: Vec<&str> = resp
+                .data
+                .iter()
+                .map(|radio| radio.attributes.title.as_str())
+                .collect();
+            assert_eq!(titles[2], "《死亡空间》回来了！GadioNews07.24");
+            let durations: Vec<u16> = resp
+                .episodes()
+                .iter()
+                .map(|(_, med)| med.attributes.duration)
+                .collect();
+            assert_eq!(durations, vec![2830, 3474, 4041]);
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn follow_pages() -> Result<(), Box<dyn Error>> {
+            let json: String = fs::read_to_string("api_response.json")?;
+            let fake = Arc::new(Memory::new(json));
+            let param = Param {
+                url: "http://fake/radios".to_owned(),
+                start: 0u32,
+                size: 3u16,
+                limit: Some(5),
+                delay: Some(0),
+                ..Default::default()
+            };
+            let resp = Client::new(fake.clone()).fetch(param).await?;
+            assert_eq!(resp.data.len(), 5);
+            let offsets: Vec<bool> = fake
+                .requested()
+                .iter()
+                .zip(["page[offset]=0&", "page[offset]=3&"].iter())
+                .map(|(url, offset)| url.contains(offset))
+                .collect();
+            assert_eq!(offsets, vec![true, true]);
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn stop_at_pages() -> Result<(), Box<dyn Error>> {
+            let json: String = fs::read_to_string("api_response.json")?;
+            let fake = Arc::new(Memory::new(json));
+            let param = Param {
+                url: "http://fake/radios".to_owned(),
+                start: 0u32,
+                size: 3u16,
+                limit: Some(100),
+                pages: Some(2),
+                delay: Some(0),
+                ..Default::default()
+            };
+            let resp = Client::new(fake.clone()).fetch(param.clone()).await?;
+            assert_eq!(fake.requested().len(), 2);
+            assert_eq!(resp.data.len(), 6);
+
+            //pages alone follow the api as far as asked
+            let param = Param {
+                limit: None,
+                pages: Some(3),
+                ..param
+            };
+            Client::new(fake.clone()).fetch(param).await?;
+            assert_eq!(fake.requested().len(), 5);
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn stop_at_until() -> Result<(), Box<dyn Error>> {
+            let json: String = fs::read_to_string("api_response.json")?;
+            let fake = Arc::new(Memory::new(json));
+            let param = Param {
+                url: "http://fake/radios".to_owned(),
+                start: 0u32,
+                size: 3u16,
+                until: Some("2021-07-25T00:00:00+08:00".to_owned()),
+                delay: Some(0),
+                ..Default::default()
+            };
+            let resp = Client::new(fake.clone()).fetch(param).await?;
+            assert_eq!(fake.requested().len(), 1);
+            assert_eq!(resp.data.len(), 2);
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn page_until_enough_matches() -> Result<(), Box<dyn Error>> {
+            let json: String = fs::read_to_string("api_response.json")?;
+            let fake = Arc::new(Memory::new(json));
+            let param = Param {
+                url: "http://fake/radios".to_owned(),
+                start: 0u32,
+                size: 3u16,
+                limit: Some(2),
+                delay: Some(0),
+                filter: Some(Filter {
+                    include: vec!["GadioNews".to_owned()],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            let resp = Client::new(fake.clone()).fetch(param).await?;
+            //one match per page of the fixture
+            assert_eq!(fake.requested().len(), 2);
+            assert_eq!(resp.data.len(), 2);
+            assert!(resp
+                .data
+                .iter()
+                .all(|r| r.attributes.title.contains("GadioNews")));
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn limit_counts_episodes() -> Result<(), Box<dyn Error>> {
+            let linkage = serde_json::json!({"type": "medias", "id": "m"});
+            let page = serde_json::json!({
+                "data": [radio("1", linkage.clone()), radio("2", serde_json::json!(null)), radio("3", linkage)],
+                "included": [media("m")]
+            });
+            let fake = Arc::new(Memory::new(page.to_string()));
+            let param = Param {
+                url: "http://fake/radios".to_owned(),
+                start: 0u32,
+                size: 3u16,
+                limit: Some(4),
+                delay: Some(0),
+                ..Default::default()
+            };
+            let resp = Client::new(fake.clone()).fetch(param).await?;
+            //two episodes a page, the radio without media does not count
+            assert_eq!(fake.requested().len(), 2);
+            assert_eq!(resp.episodes().len(), 4);
             Ok(())
         }
     }
@@ -1,25 +1,282 @@
+use crate::error::Error;
 use crate::model::api::Response;
-use crate::model::Channel;
-use std::error::Error;
+use crate::model::{Channel, Episode};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::str::FromStr;
+
+//elements that change on every render while the feed stays the same
+const VOLATILE: [&str; 1] = ["lastBuildDate"];
+
+//sha256 hex of a rendered feed, volatile elements left out without copying the document
+pub fn content_hash(doc: &[u8]) -> String {
+    let find = |hay: &[u8], needle: &[u8]| hay.windows(needle.len()).position(|w| w == needle);
+    let tags: Vec<(String, String)> = VOLATILE
+        .iter()
+        .map(|tag| (format!("<{}>", tag), format!("</{}>", tag)))
+        .collect();
+    let mut hasher = Sha256::new();
+    let mut rest = doc;
+    loop {
+        let next = tags
+            .iter()
+            .filter_map(|(open, close)| Some((find(rest, open.as_bytes())?, close)))
+            .min_by_key(|(start, _)| *start);
+        let (start, close) = match next {
+            Some(next) => next,
+            None => break,
+        };
+        match find(&rest[start..], close.as_bytes()) {
+            Some(end) => {
+                hasher.update(&rest[..start]);
+                rest = &rest[start + end + close.len()..];
+            }
+            None => break,
+        }
+    }
+    hasher.update(rest);
+    hex::encode(hasher.finalize())
+}
+
+//feed flavour picked by the `format` of a request
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Rss,
+    Atom,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "rss" => Ok(Format::Rss),
+            "atom" => Ok(Format::Atom),
+            "json" => Ok(Format::Json),
+            other => Err(Error::config(format!("unknown format {}", other))),
+        }
+    }
+}
+
+impl Format {
+    pub fn serializer(self) -> Box<dyn Serializer + Send + Sync> {
+        match self {
+            Format::Rss => Box::new(itune::Client::default()),
+            Format::Atom => Box::new(atom::Client),
+            Format::Json => Box::new(json::Client),
+        }
+    }
+
+    //Content-Type a stored feed is served with
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Rss => "application/xml",
+            Format::Atom => "application/atom+xml",
+            Format::Json => "application/feed+json",
+        }
+    }
+}
 
 pub trait Serializer {
-    fn to_xml(&self, ch: &Channel, resp: &Response) -> Result<String, Box<dyn Error>>;
+    //stream the feed document holding the given episodes into `out`
+    fn write(&self, ch: &Channel, episodes: &[Episode], out: &mut dyn Write) -> Result<(), Error>;
+
+    //episodes of a document previously produced by `write`
+    fn parse(&self, doc: &str) -> Result<Vec<Episode>, Error>;
+
+    //the whole document in memory
+    fn render(&self, ch: &Channel, episodes: &[Episode]) -> Result<String, Error> {
+        let mut data = vec![];
+        self.write(ch, episodes, &mut data)?;
+        Ok(String::from_utf8(data)?)
+    }
+
+    fn write_response(
+        &self,
+        ch: &Channel,
+        resp: &Response,
+        out: &mut dyn Write,
+    ) -> Result<(), Error> {
+        self.write(ch, &Episode::from_response(resp, ch)?, out)
+    }
+
+    fn to_xml(&self, ch: &Channel, resp: &Response) -> Result<String, Error> {
+        self.render(ch, &Episode::from_response(resp, ch)?)
+    }
+}
+
+//seconds to HH:MM:SS
+fn hms(secs: u32) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+//HH:MM:SS, MM:SS or plain seconds
+fn parse_hms(s: &str) -> Option<u32> {
+    s.trim()
+        .split(':')
+        .try_fold(0u32, |acc, part| Some(acc * 60 + part.parse::<u32>().ok()?))
+}
+
+//one element inside an item
+#[derive(Default)]
+struct Element {
+    attrs: HashMap<String, String>,
+    text: String,
+}
+
+//elements of an item by tag in document order;
+//a rel attribute joins the tag, as in atom's link[enclosure]
+#[derive(Default)]
+struct Fields(HashMap<String, Vec<Element>>);
+
+impl Fields {
+    //text of the first `tag` element, or its attribute for `tag@attr`
+    fn get(&self, key: &str) -> Option<&String> {
+        let (tag, attr) = match key.split_once('@') {
+            Some((tag, attr)) => (tag, Some(attr)),
+            None => (key, None),
+        };
+        let ele = self.elements(tag).first()?;
+        match attr {
+            Some(attr) => ele.attrs.get(attr),
+            None => Some(&ele.text),
+        }
+    }
+
+    fn elements(&self, tag: &str) -> &[Element] {
+        self.0.get(tag).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    fn set_text(&mut self, tag: &str, text: String) {
+        if let Some(ele) = self.0.get_mut(tag).and_then(|eles| eles.last_mut()) {
+            ele.text = text;
+        }
+    }
+}
+
+//cdata content as written; the reader hands it over through quick-xml's escape,
+//only those five replacements are undone so entities like &nbsp; stay as they are
+fn cdata(escaped: &str) -> String {
+    escaped
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&apos;", "'")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+//fields of every `item_tag` element of a document whose root is `root`,
+//a feed stored in another format is an error rather than an empty history
+fn read_items(doc: &str, root: &str, item_tag: &str) -> Result<Vec<Fields>, Error> {
+    if doc.trim_start().starts_with('{') {
+        return Err(Error::decode(format!(
+            "stored feed is json, expected <{}>; was param.format changed?",
+            root
+        )));
+    }
+    let mut reader = Reader::from_str(doc);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut items = vec![];
+    //fields of the item being read
+    let mut item: Option<Fields> = None;
+    let mut tag = String::new();
+    let mut rooted = false;
+
+    loop {
+        let event = reader.read_event(&mut buf)?;
+        if !rooted {
+            match &event {
+                Event::Start(e) | Event::Empty(e) if e.name() != root.as_bytes() => {
+                    return Err(Error::decode(format!(
+                        "stored feed has root <{}>, expected <{}>; was param.format changed?",
+                        reader.decode(e.name())?,
+                        root
+                    )));
+                }
+                Event::Start(_) | Event::Empty(_) => rooted = true,
+                Event::Eof => {
+                    return Err(Error::decode(format!(
+                        "stored feed has no <{}> element",
+                        root
+                    )))
+                }
+                _ => {}
+            }
+        }
+        match event {
+            Event::Start(e) | Event::Empty(e)
+                if item.is_some() || e.name() == item_tag.as_bytes() =>
+            {
+                tag = reader.decode(e.name())?.to_string();
+                if tag == item_tag {
+                    item = Some(Fields::default());
+                } else if let Some(fields) = item.as_mut() {
+                    let mut ele = Element::default();
+                    for attr in e.attributes() {
+                        let attr = attr?;
+                        ele.attrs.insert(
+                            reader.decode(attr.key)?.to_string(),
+                            attr.unescape_and_decode_value(&reader)?,
+                        );
+                    }
+                    if let Some(rel) = ele.attrs.get("rel") {
+                        tag = format!("{}[{}]", tag, rel);
+                    }
+                    fields.0.entry(tag.clone()).or_default().push(ele);
+                }
+            }
+            Event::Text(e) => {
+                if let Some(fields) = item.as_mut() {
+                    fields.set_text(&tag, e.unescape_and_decode(&reader)?);
+                }
+            }
+            Event::CData(e) => {
+                if let Some(fields) = item.as_mut() {
+                    fields.set_text(&tag, cdata(reader.decode(&e)?));
+                }
+            }
+            Event::End(e) if e.name() == item_tag.as_bytes() => {
+                if let Some(fields) = item.take() {
+                    items.push(fields);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(items)
 }
 
 pub mod itune {
-    use crate::model::{
-        api::{inc, Radio, Response},
-        Channel,
-    };
+    use super::{hms, parse_hms, read_items, Fields};
+    use crate::error::Error;
+    use crate::model::{Channel, Episode, ItunesCategory, Person};
+    use chrono::{DateTime, Utc};
     use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
     use quick_xml::Writer;
-    use std::error::Error;
-    use std::io::Cursor;
+    use std::io::Write;
 
     pub struct Client<'a> {
         version: (&'a str, &'a str),
         xmlns: (&'a str, &'a str),
         prefix: &'a str,
+        //declared only when the channel configures podcast tags
+        podcast_xmlns: (&'a str, &'a str),
+        podcast_prefix: &'a str,
         xml_version: &'a str,
         xml_encode: &'a str,
     }
@@ -39,13 +296,23 @@ pub mod itune {
     const ITEM: &str = "item";
     const CLOSURE: &str = "enclosure";
     const URL: &str = "url";
+    const LENGTH: &str = "length";
     const DURATION: &str = "duration";
     const GUID: &str = "guid";
     const MPEG: (&str, &str) = ("type", "audio/mpeg");
     const PUBDATE: &str = "pubDate";
+    const BUILD_DATE: &str = "lastBuildDate";
     const TEXT: &str = "text";
     const IMAGE: &str = "image";
     const HREF: &str = "href";
+    const LOCKED: &str = "locked";
+    const FUNDING: &str = "funding";
+    const PERSON: &str = "person";
+    const ROLE: &str = "role";
+    const IMG: &str = "img";
+    const TRANSCRIPT: &str = "transcript";
+    const CHAPTERS: &str = "chapters";
+    const CHAPTERS_TYPE: (&str, &str) = ("type", "application/json+chapters");
 
     impl<'a> Default for Client<'a> {
         fn default() -> Self {
@@ -53,6 +320,8 @@ pub mod itune {
                 xmlns: ("xmlns:itunes", "http://www.itunes.com/dtds/podcast-1.0.dtd"),
                 version: ("version", "2.0"),
                 prefix: "itunes:",
+                podcast_xmlns: ("xmlns:podcast", "https://podcastindex.org/namespace/1.0"),
+                podcast_prefix: "podcast:",
                 xml_version: "1.0",
                 xml_encode: "UTF8",
             }
@@ -60,53 +329,249 @@ pub mod itune {
     }
 
     impl<'a> Client<'a> {
-        fn to_item(&self, radio: &'a Radio, media: &'a inc::Media, ch: &Channel) -> Vec<Event> {
+        fn to_item<'b>(&self, ch: &'b Channel, ep: &'b Episode) -> Vec<Event<'b>> {
             let item = ITEM.as_bytes();
             let title = TITLE.as_bytes();
-            let audio_url: String = format!("{}{}", ch.media_base_url, media.attributes.audio);
 
             let closure = CLOSURE.as_bytes();
             let mut closure_ele = BytesStart::borrowed(closure, closure.len());
             closure_ele.push_attribute(MPEG);
-            closure_ele.push_attribute((URL, audio_url.as_str()));
-            closure_ele.push_attribute((DURATION, media.attributes.duration.to_string().as_str()));
+            closure_ele.push_attribute((URL, ep.audio_url.as_str()));
+            closure_ele.push_attribute((LENGTH, ep.length.to_string().as_str()));
 
             let guid = GUID.as_bytes();
             let description = DESCRIPTION.as_bytes();
             let pub_date = PUBDATE.as_bytes();
 
+            let image = format!("{}{}", self.prefix, IMAGE).into_bytes();
+            let image_len = image.len();
+            let mut image_ele = BytesStart::owned(image, image_len);
+            image_ele.push_attribute((HREF, ep.image.as_str()));
+
+            let duration = format!("{}{}", self.prefix, DURATION).into_bytes();
+            let duration_len = duration.len();
+
             let link = LINK.as_bytes();
-            let web_link = format!("{}{}", ch.web_base_url, radio.id);
 
-            vec![
+            let mut events = vec![
                 Event::Start(BytesStart::borrowed(item, ITEM.len())),
                 Event::Start(BytesStart::borrowed(title, title.len())),
-                Event::Text(BytesText::from_plain_str(&radio.attributes.title)),
+                Event::Text(BytesText::from_plain_str(&ep.title)),
                 Event::End(BytesEnd::borrowed(title)),
                 Event::Start(BytesStart::borrowed(guid, guid.len())),
-                Event::Text(BytesText::from_escaped_str(audio_url)),
+                Event::Text(BytesText::from_plain_str(&ep.guid)),
                 Event::End(BytesEnd::borrowed(guid)),
                 Event::Start(BytesStart::borrowed(description, description.len())),
-                Event::CData(BytesText::from_escaped_str(radio.attributes.desc.as_str())),
+                Event::CData(BytesText::from_escaped_str(ep.description.as_str())),
                 Event::End(BytesEnd::borrowed(description)),
                 Event::Empty(closure_ele),
+                Event::Empty(image_ele),
                 Event::Start(BytesStart::borrowed(pub_date, pub_date.len())),
-                Event::Text(BytesText::from_escaped_str(
-                    radio.attributes.published_at.as_str(),
-                )),
+                Event::Text(BytesText::from_escaped_str(ep.published.to_rfc2822())),
                 Event::End(BytesEnd::borrowed(pub_date)),
+                Event::Start(BytesStart::owned(duration.clone(), duration_len)),
+                Event::Text(BytesText::from_escaped_str(hms(ep.duration))),
+                Event::End(BytesEnd::owned(duration)),
                 Event::Start(BytesStart::owned(link, LINK.len())),
-                Event::Text(BytesText::from_escaped_str(web_link)),
+                Event::Text(BytesText::from_plain_str(&ep.link)),
                 Event::End(BytesEnd::borrowed(link)),
-                Event::End(BytesEnd::borrowed(item)),
+            ];
+
+            //plain rss categories, the itunes ones are for the channel only
+            let category = CATEGORY.as_bytes();
+            for cat in ep.categories.iter() {
+                events.push(Event::Start(BytesStart::borrowed(category, category.len())));
+                events.push(Event::Text(BytesText::from_plain_str(cat)));
+                events.push(Event::End(BytesEnd::borrowed(category)));
+            }
+
+            if !ep.persons.is_empty() {
+                let names: Vec<&str> = ep.persons.iter().map(|p| p.name.as_str()).collect();
+                let author = format!("{}{}", self.prefix, AUTHOR).into_bytes();
+                let author_len = author.len();
+                events.push(Event::Start(BytesStart::owned(author.clone(), author_len)));
+                events.push(Event::Text(
+                    BytesText::from_plain_str(&names.join(", ")).into_owned(),
+                ));
+                events.push(Event::End(BytesEnd::owned(author)));
+                ep.persons
+                    .iter()
+                    .for_each(|person| events.extend(self.person_events(person)));
+            }
+
+            if let (Some(url), Some(transcript)) = (&ep.transcript, &ch.transcript) {
+                let tag = format!("{}{}", self.podcast_prefix, TRANSCRIPT).into_bytes();
+                let tag_len = tag.len();
+                let mut ele = BytesStart::owned(tag, tag_len);
+                ele.push_attribute((URL, url.as_str()));
+                ele.push_attribute(("type", transcript.kind.as_str()));
+                events.push(Event::Empty(ele));
+            }
+            if let Some(url) = &ep.chapters {
+                let tag = format!("{}{}", self.podcast_prefix, CHAPTERS).into_bytes();
+                let tag_len = tag.len();
+                let mut ele = BytesStart::owned(tag, tag_len);
+                ele.push_attribute((URL, url.as_str()));
+                ele.push_attribute(CHAPTERS_TYPE);
+                events.push(Event::Empty(ele));
+            }
+
+            events.push(Event::End(BytesEnd::borrowed(item)));
+            events
+        }
+
+        //whether `to_item` writes any podcast tag for the episode
+        fn has_podcast(&self, ch: &Channel, ep: &Episode) -> bool {
+            !ep.persons.is_empty()
+                || (ep.transcript.is_some() && ch.transcript.is_some())
+                || ep.chapters.is_some()
+        }
+
+        //<podcast:person role href img>name</podcast:person>
+        fn person_events<'b>(&self, person: &'b Person) -> Vec<Event<'b>> {
+            let tag = format!("{}{}", self.podcast_prefix, PERSON).into_bytes();
+            let tag_len = tag.len();
+            let mut ele = BytesStart::owned(tag.clone(), tag_len);
+            ele.push_attribute((ROLE, person.role.as_str()));
+            if let Some(href) = &person.href {
+                ele.push_attribute((HREF, href.as_str()));
+            }
+            if let Some(img) = &person.img {
+                ele.push_attribute((IMG, img.as_str()));
+            }
+            vec![
+                Event::Start(ele),
+                Event::Text(BytesText::from_plain_str(&person.name)),
+                Event::End(BytesEnd::owned(tag)),
             ]
         }
+
+        //<itunes:category text> with its subcategories nested
+        fn write_category<W: Write>(
+            &self,
+            writer: &mut Writer<W>,
+            cat: &ItunesCategory,
+        ) -> Result<(), Error> {
+            let cat_str = format!("{}{}", self.prefix, CATEGORY);
+            let mut ele = BytesStart::borrowed_name(cat_str.as_bytes());
+            ele.push_attribute((TEXT, cat.text.as_str()));
+            if cat.subcategories.is_empty() {
+                writer.write_event(Event::Empty(ele))?;
+                return Ok(());
+            }
+            writer.write_event(Event::Start(ele))?;
+            for sub in cat.subcategories.iter() {
+                self.write_category(writer, sub)?;
+            }
+            writer.write_event(Event::End(BytesEnd::borrowed(cat_str.as_bytes())))?;
+            Ok(())
+        }
+
+        //channel level podcast namespace tags
+        fn write_podcast<W: Write>(
+            &self,
+            writer: &mut Writer<W>,
+            ch: &Channel,
+        ) -> Result<(), Error> {
+            let tag = |name: &str| format!("{}{}", self.podcast_prefix, name);
+
+            if let Some(locked) = ch.locked {
+                let locked_tag = tag(LOCKED);
+                let mut ele = BytesStart::borrowed_name(locked_tag.as_bytes());
+                ele.push_attribute((OWNER, ch.owner_email.as_str()));
+                writer.write_event(Event::Start(ele))?;
+                writer.write_event(Event::Text(BytesText::from_plain_str(if locked {
+                    "yes"
+                } else {
+                    "no"
+                })))?;
+                writer.write_event(Event::End(BytesEnd::borrowed(locked_tag.as_bytes())))?;
+            }
+
+            if let Some(guid) = &ch.podcast_guid {
+                let guid_tag = tag(GUID);
+                writer.write_event(Event::Start(BytesStart::borrowed_name(guid_tag.as_bytes())))?;
+                writer.write_event(Event::Text(BytesText::from_plain_str(guid)))?;
+                writer.write_event(Event::End(BytesEnd::borrowed(guid_tag.as_bytes())))?;
+            }
+
+            if let Some(funding) = &ch.funding {
+                let funding_tag = tag(FUNDING);
+                let mut ele = BytesStart::borrowed_name(funding_tag.as_bytes());
+                ele.push_attribute((URL, funding.url.as_str()));
+                writer.write_event(Event::Start(ele))?;
+                writer.write_event(Event::Text(BytesText::from_plain_str(&funding.text)))?;
+                writer.write_event(Event::End(BytesEnd::borrowed(funding_tag.as_bytes())))?;
+            }
+
+            ch.persons
+                .iter()
+                .flat_map(|person| self.person_events(person))
+                .try_for_each(|ev| writer.write_event(ev))?;
+            Ok(())
+        }
+
+        fn read_item(&self, fields: &Fields) -> Result<Episode, Error> {
+            let field = |key: &str| fields.get(key).cloned().unwrap_or_default();
+            let itunes = |name: &str| field(&format!("{}{}", self.prefix, name));
+
+            let pub_date = field(PUBDATE);
+            //feeds written before pubDate was rfc 2822 carry the api timestamp
+            let published = DateTime::parse_from_rfc2822(&pub_date)
+                .or_else(|_| DateTime::parse_from_rfc3339(&pub_date))?;
+
+            //as well as the duration in seconds on the enclosure
+            let duration = match fields.get(&format!("{}{}", self.prefix, DURATION)) {
+                Some(d) => parse_hms(d),
+                None => field(&format!("{}@{}", CLOSURE, DURATION)).parse().ok(),
+            };
+
+            Ok(Episode {
+                guid: field(GUID),
+                title: field(TITLE),
+                description: field(DESCRIPTION),
+                link: field(LINK),
+                audio_url: field(&format!("{}@{}", CLOSURE, URL)),
+                length: field(&format!("{}@{}", CLOSURE, LENGTH))
+                    .parse()
+                    .unwrap_or(0),
+                duration: duration.unwrap_or(0),
+                image: itunes(&format!("{}@{}", IMAGE, HREF)),
+                published,
+                transcript: fields
+                    .get(&format!("{}{}@{}", self.podcast_prefix, TRANSCRIPT, URL))
+                    .cloned(),
+                chapters: fields
+                    .get(&format!("{}{}@{}", self.podcast_prefix, CHAPTERS, URL))
+                    .cloned(),
+                persons: fields
+                    .elements(&format!("{}{}", self.podcast_prefix, PERSON))
+                    .iter()
+                    .map(|ele| Person {
+                        name: ele.text.clone(),
+                        role: ele.attrs.get(ROLE).cloned().unwrap_or_default(),
+                        href: ele.attrs.get(HREF).cloned(),
+                        img: ele.attrs.get(IMG).cloned(),
+                    })
+                    .collect(),
+                categories: fields
+                    .elements(CATEGORY)
+                    .iter()
+                    .map(|ele| ele.text.clone())
+                    .collect(),
+            })
+        }
     }
 
     impl<'a> super::Serializer for Client<'a> {
-        fn to_xml(&self, ch: &Channel, resp: &Response) -> Result<String, Box<dyn Error>> {
+        fn write(
+            &self,
+            ch: &Channel,
+            episodes: &[Episode],
+            out: &mut dyn Write,
+        ) -> Result<(), Error> {
             //ascii space 32
-            let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), 32u8, 2);
+            let mut writer = Writer::new_with_indent(out, 32u8, 2);
             writer.write_event(Event::Decl(BytesDecl::new(
                 self.xml_version.as_bytes(),
                 Some(self.xml_encode.as_bytes()),
@@ -115,6 +580,9 @@ pub mod itune {
             //rss tag
             let mut rss_tag = BytesStart::owned(RSS.as_bytes(), RSS.len());
             rss_tag.push_attribute(self.xmlns);
+            if ch.has_podcast() || episodes.iter().any(|ep| self.has_podcast(ch, ep)) {
+                rss_tag.push_attribute(self.podcast_xmlns);
+            }
             rss_tag.push_attribute(self.version);
             writer.write_event(Event::Start(rss_tag))?;
 
@@ -156,23 +624,17 @@ pub mod itune {
             writer.write_event(Event::End(BytesEnd::borrowed(LANGUAGE.as_bytes())))?;
 
             //category
-            let cat_str = format!("{}{}", self.prefix, CATEGORY);
-            let arr: &[u8] = cat_str.as_bytes();
-            let arr_len = arr.len();
-            let mut cat1 = BytesStart::owned(arr, arr_len);
-            cat1.push_attribute((TEXT, ch.category1.as_ref()));
-            writer.write_event(Event::Start(cat1))?;
-
-            let mut cat2 = BytesStart::owned(arr, arr_len);
-            cat2.push_attribute((TEXT, ch.category2.as_ref()));
-            writer.write_event(Event::Empty(cat2))?;
-            writer.write_event(Event::End(BytesEnd::borrowed(arr)))?;
+            for cat in ch.itunes_categories().iter() {
+                self.write_category(&mut writer, cat)?;
+            }
 
             //explicit
             let itune_explicit = format!("{}{}", self.prefix, EXPLICIT);
             let explicit = itune_explicit.as_bytes();
             writer.write_event(Event::Start(BytesStart::owned(explicit, explicit.len())))?;
-            writer.write_event(Event::Text(BytesText::from_plain_str(&ch.explicit)))?;
+            writer.write_event(Event::Text(BytesText::from_plain_str(
+                &ch.explicit.to_string(),
+            )))?;
             writer.write_event(Event::End(BytesEnd::borrowed(explicit)))?;
 
             //author
@@ -189,6 +651,17 @@ pub mod itune {
             writer.write_event(Event::Text(BytesText::from_plain_str(&ch.link)))?;
             writer.write_event(Event::End(BytesEnd::borrowed(link)))?;
 
+            //build date
+            let build_date = BUILD_DATE.as_bytes();
+            writer.write_event(Event::Start(BytesStart::owned(
+                build_date,
+                BUILD_DATE.len(),
+            )))?;
+            writer.write_event(Event::Text(BytesText::from_escaped_str(
+                Utc::now().to_rfc2822(),
+            )))?;
+            writer.write_event(Event::End(BytesEnd::borrowed(build_date)))?;
+
             //owner
             let owner = format!("{}{}", self.prefix, OWNER);
             let name = format!("{}{}", self.prefix, NAME);
@@ -208,11 +681,13 @@ pub mod itune {
             writer.write_event(Event::End(BytesEnd::borrowed(email.as_bytes())))?;
             writer.write_event(Event::End(BytesEnd::borrowed(owner.as_bytes())))?;
 
+            //podcast namespace
+            self.write_podcast(&mut writer, ch)?;
+
             //item
-            resp.data
+            episodes
                 .iter()
-                .zip(resp.included.iter())
-                .flat_map(|(radio, media)| self.to_item(radio, media, ch))
+                .flat_map(|ep| self.to_item(ch, ep))
                 .try_for_each(|ev| writer.write_event(ev))?;
 
             //end
@@ -220,17 +695,22 @@ pub mod itune {
             writer.write_event(Event::End(BytesEnd::borrowed(RSS.as_bytes())))?;
 
             writer.write_event(Event::Eof)?;
+            Ok(())
+        }
 
-            let data = writer.into_inner().into_inner();
-            Ok(String::from_utf8(data)?)
+        fn parse(&self, doc: &str) -> Result<Vec<Episode>, Error> {
+            read_items(doc, RSS, ITEM)?
+                .iter()
+                .map(|fields| self.read_item(fields))
+                .collect()
         }
     }
 
     #[cfg(test)]
     mod tests {
         use super::Client;
-        use crate::model::{api::Response, Channel};
-        use crate::se::Serializer;
+        use crate::model::{api::Response, Channel, Episode};
+        use crate::se::{content_hash, Serializer};
         use std::error::Error;
         use std::fs;
 
@@ -246,18 +726,734 @@ pub mod itune {
                 owner_name: "some owner".to_string(),
                 owner_email: "some@eee.com".to_string(),
                 media_base_url: "https://example.com/media/".to_string(),
-                explicit: "true".to_string(),
+                explicit: true,
                 language: "test language".to_string(),
                 category1: "Travel".to_string(),
                 category2: "cook".to_string(),
                 web_base_url: "http::/exm.com/pages/".to_string(),
+                image_base_url: "https://example.com/img/".to_string(),
                 ..Default::default()
             };
             let json: String = fs::read_to_string("api_response.json")?;
-            let response: Response = serde_json::from_str(&json)?;
+            let mut response: Response = serde_json::from_str(&json)?;
+            response.data[1].attributes.thumb.clear();
             let xml_str = itune.to_xml(&ch, &response)?;
-            println!("{}", xml_str);
+            assert!(xml_str.contains("<pubDate>Tue, 27 Jul 2021 22:00:00 +0800</pubDate>"));
+            assert!(xml_str.contains("<itunes:duration>00:47:10</itunes:duration>"));
+            assert!(xml_str.contains(r#"length="0""#));
+            assert!(!xml_str.contains("xmlns:podcast"));
+            assert!(xml_str.contains(
+                r#"<itunes:category text="Travel">
+      <itunes:category text="cook"/>
+    </itunes:category>"#
+            ));
+            assert!(xml_str.contains(
+                r#"<itunes:image href="https://example.com/img/fe15d545-8434-497d-82de-4081ea5036cd.jpg"/>"#
+            ));
+            assert_eq!(
+                xml_str
+                    .matches(r#"<itunes:image href="http://www.example.com/podcast-icon.jpg"/>"#)
+                    .count(),
+                2
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn duration_hms() {
+            use crate::se::{hms, parse_hms};
+            assert_eq!(hms(0), "00:00:00");
+            assert_eq!(hms(4041), "01:07:21");
+            assert_eq!(parse_hms("01:07:21"), Some(4041));
+            assert_eq!(parse_hms("07:21"), Some(441));
+            assert_eq!(parse_hms("4041"), Some(4041));
+            assert_eq!(parse_hms("1:x"), None);
+        }
+
+        #[test]
+        fn parse_rendered() -> Result<(), Box<dyn Error>> {
+            let itune = Client::default();
+            let ch = Channel {
+                media_base_url: "https://example.com/media/".to_string(),
+                web_base_url: "https://example.com/radios/".to_string(),
+                image: "http://www.example.com/podcast-icon.jpg".to_string(),
+                ..Default::default()
+            };
+            let json: String = fs::read_to_string("api_response.json")?;
+            let response: Response = serde_json::from_str(&json)?;
+            let episodes = Episode::from_response(&response, &ch)?;
+            //no image_base_url, the thumbs can not be resolved
+            assert!(episodes.iter().all(|ep| ep.image == ch.image));
+            let xml_str = itune.render(&ch, &episodes)?;
+            assert_eq!(itune.parse(&xml_str)?, episodes);
+            Ok(())
+        }
+
+        #[test]
+        fn podcast_namespace() -> Result<(), Box<dyn Error>> {
+            let itune = Client::default();
+            let ch: Channel = serde_json::from_value(serde_json::json!({
+                "title": "t", "author": "a", "description": "d", "image": "i",
+                "language": "zh-cn", "category1": "c1", "category2": "c2",
+                "link": "https://example.com", "owner_name": "o",
+                "owner_email": "o@example.com", "explicit": "false",
+                "media_base_url": "https://example.com/media/",
+                "web_base_url": "https://example.com/radios/",
+                "podcast_guid": "917393e3-1b1e-5cef-ace4-edaa54e1f810",
+                "locked": true,
+                "funding": { "url": "https://example.com/support", "text": "support us" },
+                "persons": [{ "name": "Host A" }, { "name": "Guest B", "role": "guest" }],
+                "transcript": { "url": "https://example.com/t/{id}.vtt", "ids": ["139509"] },
+                "chapters": { "url": "https://example.com/c/{id}.json", "ids": ["139509"] }
+            }))?;
+            let json: String = fs::read_to_string("api_response.json")?;
+            let response: Response = serde_json::from_str(&json)?;
+            let episodes = Episode::from_response(&response, &ch)?;
+            let xml_str = itune.render(&ch, &episodes)?;
+            assert!(xml_str.contains(r#"xmlns:podcast="https://podcastindex.org/namespace/1.0""#));
+            assert!(
+                xml_str.contains(r#"<podcast:locked owner="o@example.com">yes</podcast:locked>"#)
+            );
+            assert!(xml_str
+                .contains("<podcast:guid>917393e3-1b1e-5cef-ace4-edaa54e1f810</podcast:guid>"));
+            assert!(xml_str.contains(
+                r#"<podcast:funding url="https://example.com/support">support us</podcast:funding>"#
+            ));
+            assert!(xml_str.contains(r#"<podcast:person role="host">Host A</podcast:person>"#));
+            assert!(xml_str.contains(r#"<podcast:person role="guest">Guest B</podcast:person>"#));
+            assert!(xml_str.contains(
+                r#"<podcast:transcript url="https://example.com/t/139509.vtt" type="text/vtt"/>"#
+            ));
+            assert!(xml_str.contains(
+                r#"<podcast:chapters url="https://example.com/c/139509.json" type="application/json+chapters"/>"#
+            ));
+            //only the listed radio has them
+            assert_eq!(xml_str.matches("<podcast:transcript").count(), 1);
+            assert_eq!(xml_str.matches("<podcast:chapters").count(), 1);
+            assert_eq!(itune.parse(&xml_str)?, episodes);
+
+            //stored items keep their chapters after the channel drops the podcast fields
+            let plain = Channel {
+                image: "i".to_string(),
+                ..Default::default()
+            };
+            let stored = itune.parse(&xml_str)?;
+            let mut stored: Vec<Episode> = stored
+                .into_iter()
+                .filter(|ep| ep.chapters.is_some())
+                .collect();
+            stored[0].persons.clear();
+            let xml_str = itune.render(&plain, &stored)?;
+            assert!(xml_str.contains("<podcast:chapters"));
+            assert!(xml_str.contains(r#"xmlns:podcast="https://podcastindex.org/namespace/1.0""#));
+            Ok(())
+        }
+
+        #[test]
+        fn nested_categories() -> Result<(), Box<dyn Error>> {
+            let itune = Client::default();
+            let ch: Channel = serde_json::from_value(serde_json::json!({
+                "title": "t", "author": "a", "description": "d", "image": "i",
+                "language": "zh-cn", "link": "https://example.com", "owner_name": "o",
+                "owner_email": "o@example.com", "explicit": "false",
+                "media_base_url": "https://example.com/media/",
+                "web_base_url": "https://example.com/radios/",
+                "categories": [
+                    { "text": "Leisure", "subcategories": [
+                        { "text": "Video Games" }, { "text": "Hobbies" }
+                    ]},
+                    { "text": "News" }
+                ]
+            }))?;
+            let json: String = fs::read_to_string("api_response.json")?;
+            let response: Response = serde_json::from_str(&json)?;
+            let mut episodes = Episode::from_response(&response, &ch)?;
+            episodes[0].categories = vec!["游戏新闻".to_string(), "桌游".to_string()];
+            let xml_str = itune.render(&ch, &episodes)?;
+            assert!(xml_str.contains(
+                r#"<itunes:category text="Leisure">
+      <itunes:category text="Video Games"/>
+      <itunes:category text="Hobbies"/>
+    </itunes:category>
+    <itunes:category text="News"/>"#
+            ));
+            assert!(xml_str.contains("<category>游戏新闻</category>"));
+            assert_eq!(xml_str.matches("<category>").count(), 2);
+            assert_eq!(itune.parse(&xml_str)?, episodes);
+            Ok(())
+        }
+
+        #[test]
+        fn hash_ignores_build_date() -> Result<(), Box<dyn Error>> {
+            let itune = Client::default();
+            let ch = Channel::default();
+            let json: String = fs::read_to_string("api_response.json")?;
+            let response: Response = serde_json::from_str(&json)?;
+            let episodes = Episode::from_response(&response, &ch)?;
+            let xml_str = itune.render(&ch, &episodes)?;
+            let start = xml_str.find("<lastBuildDate>").unwrap() + "<lastBuildDate>".len();
+            let end = xml_str.find("</lastBuildDate>").unwrap();
+            let rebuilt = format!(
+                "{}Thu, 01 Jan 1970 00:00:00 +0000{}",
+                &xml_str[..start],
+                &xml_str[end..]
+            );
+            assert_ne!(rebuilt, xml_str);
+            assert_eq!(
+                content_hash(rebuilt.as_bytes()),
+                content_hash(xml_str.as_bytes())
+            );
+            let fewer = itune.render(&ch, &episodes[1..])?;
+            assert_ne!(
+                content_hash(fewer.as_bytes()),
+                content_hash(xml_str.as_bytes())
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn parse_legacy_item() -> Result<(), Box<dyn Error>> {
+            let xml_str = r#"<rss><channel><title>t</title>
+                <item>
+                  <title>a &amp; b</title>
+                  <guid>https://example.com/media/a.mp3</guid>
+                  <description><![CDATA[<p>desc&nbsp;&amp; "more"</p>]]></description>
+                  <enclosure type="audio/mpeg" url="https://example.com/media/a.mp3" duration="2830"/>
+                  <pubDate>2021-07-27T22:00:00.000+08:00</pubDate>
+                  <link>https://example.com/radios/1</link>
+                </item></channel></rss>"#;
+            let episodes = Client::default().parse(xml_str)?;
+            assert_eq!(episodes.len(), 1);
+            assert_eq!(episodes[0].title, "a & b");
+            assert_eq!(episodes[0].description, r#"<p>desc&nbsp;&amp; "more"</p>"#);
+            assert_eq!(episodes[0].duration, 2830);
+            assert_eq!(
+                episodes[0].published.to_rfc2822(),
+                "Tue, 27 Jul 2021 22:00:00 +0800"
+            );
             Ok(())
         }
     }
 }
+
+pub mod atom {
+    use super::{hms, parse_hms, read_items, Fields};
+    use crate::error::Error;
+    use crate::model::{Channel, Episode};
+    use chrono::{DateTime, Utc};
+    use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+    use quick_xml::Writer;
+    use std::io::Write;
+
+    type XmlWriter<'w> = Writer<&'w mut dyn Write>;
+
+    const XMLNS: (&str, &str) = ("xmlns", "http://www.w3.org/2005/Atom");
+    //artwork and duration have no atom element, podcast apps read the itunes ones
+    const XMLNS_ITUNES: (&str, &str) =
+        ("xmlns:itunes", "http://www.itunes.com/dtds/podcast-1.0.dtd");
+    const FEED: &str = "feed";
+    const ENTRY: &str = "entry";
+    const ID: &str = "id";
+    const TITLE: &str = "title";
+    const SUBTITLE: &str = "subtitle";
+    const SUMMARY: &str = "summary";
+    const UPDATED: &str = "updated";
+    const PUBLISHED: &str = "published";
+    const LINK: &str = "link";
+    const AUTHOR: &str = "author";
+    const NAME: &str = "name";
+    const EMAIL: &str = "email";
+    const CATEGORY: &str = "category";
+    const TERM: &str = "term";
+    const LOGO: &str = "logo";
+    const ITUNES_IMAGE: &str = "itunes:image";
+    const ITUNES_DURATION: &str = "itunes:duration";
+    const ALTERNATE: &str = "alternate";
+    const ENCLOSURE: &str = "enclosure";
+    const MPEG: &str = "audio/mpeg";
+
+    pub struct Client;
+
+    fn start(writer: &mut XmlWriter<'_>, tag: &str, attrs: &[(&str, &str)]) -> Result<(), Error> {
+        let mut ele = BytesStart::borrowed_name(tag.as_bytes());
+        attrs.iter().for_each(|attr| ele.push_attribute(*attr));
+        writer.write_event(Event::Start(ele))?;
+        Ok(())
+    }
+
+    fn end(writer: &mut XmlWriter<'_>, tag: &str) -> Result<(), Error> {
+        writer.write_event(Event::End(BytesEnd::borrowed(tag.as_bytes())))?;
+        Ok(())
+    }
+
+    fn empty(writer: &mut XmlWriter<'_>, tag: &str, attrs: &[(&str, &str)]) -> Result<(), Error> {
+        let mut ele = BytesStart::borrowed_name(tag.as_bytes());
+        attrs.iter().for_each(|attr| ele.push_attribute(*attr));
+        writer.write_event(Event::Empty(ele))?;
+        Ok(())
+    }
+
+    fn text(
+        writer: &mut XmlWriter<'_>,
+        tag: &str,
+        attrs: &[(&str, &str)],
+        value: &str,
+    ) -> Result<(), Error> {
+        start(writer, tag, attrs)?;
+        writer.write_event(Event::Text(BytesText::from_plain_str(value)))?;
+        end(writer, tag)
+    }
+
+    fn author(writer: &mut XmlWriter<'_>, ch: &Channel) -> Result<(), Error> {
+        start(writer, AUTHOR, &[])?;
+        text(writer, NAME, &[], &ch.author)?;
+        if !ch.owner_email.is_empty() {
+            text(writer, EMAIL, &[], &ch.owner_email)?;
+        }
+        end(writer, AUTHOR)
+    }
+
+    impl Client {
+        fn write_entry(
+            &self,
+            writer: &mut XmlWriter<'_>,
+            ch: &Channel,
+            ep: &Episode,
+        ) -> Result<(), Error> {
+            let published = ep.published.to_rfc3339();
+            start(writer, ENTRY, &[])?;
+            text(writer, ID, &[], &ep.guid)?;
+            text(writer, TITLE, &[], &ep.title)?;
+            text(writer, UPDATED, &[], &published)?;
+            text(writer, PUBLISHED, &[], &published)?;
+            empty(writer, LINK, &[("rel", ALTERNATE), ("href", &ep.link)])?;
+            empty(
+                writer,
+                LINK,
+                &[
+                    ("rel", ENCLOSURE),
+                    ("type", MPEG),
+                    ("href", &ep.audio_url),
+                    ("length", &ep.length.to_string()),
+                ],
+            )?;
+            text(writer, SUMMARY, &[("type", "html")], &ep.description)?;
+            for cat in ep.categories.iter() {
+                empty(writer, CATEGORY, &[(TERM, cat)])?;
+            }
+            author(writer, ch)?;
+            empty(writer, ITUNES_IMAGE, &[("href", &ep.image)])?;
+            text(writer, ITUNES_DURATION, &[], &hms(ep.duration))?;
+            end(writer, ENTRY)
+        }
+
+        fn read_entry(&self, fields: &Fields) -> Result<Episode, Error> {
+            let field = |key: &str| fields.get(key).cloned().unwrap_or_default();
+            let published = match fields.get(PUBLISHED) {
+                Some(p) => p.clone(),
+                None => field(UPDATED),
+            };
+            Ok(Episode {
+                guid: field(ID),
+                title: field(TITLE),
+                description: field(SUMMARY),
+                link: field(&format!("{}[{}]@href", LINK, ALTERNATE)),
+                audio_url: field(&format!("{}[{}]@href", LINK, ENCLOSURE)),
+                length: field(&format!("{}[{}]@length", LINK, ENCLOSURE))
+                    .parse()
+                    .unwrap_or(0),
+                duration: parse_hms(&field(ITUNES_DURATION)).unwrap_or(0),
+                image: field(&format!("{}@href", ITUNES_IMAGE)),
+                published: DateTime::parse_from_rfc3339(&published)?,
+                transcript: None,
+                chapters: None,
+                persons: vec![],
+                categories: fields
+                    .elements(CATEGORY)
+                    .iter()
+                    .filter_map(|ele| ele.attrs.get(TERM).cloned())
+                    .collect(),
+            })
+        }
+    }
+
+    impl super::Serializer for Client {
+        fn write(
+            &self,
+            ch: &Channel,
+            episodes: &[Episode],
+            out: &mut dyn Write,
+        ) -> Result<(), Error> {
+            //ascii space 32
+            let mut writer = Writer::new_with_indent(out, 32u8, 2);
+            writer.write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
+
+            let mut feed_attrs = vec![XMLNS, XMLNS_ITUNES];
+            if !ch.language.is_empty() {
+                feed_attrs.push(("xml:lang", &ch.language));
+            }
+            start(&mut writer, FEED, &feed_attrs)?;
+
+            text(&mut writer, ID, &[], &ch.link)?;
+            text(&mut writer, TITLE, &[], &ch.title)?;
+            text(&mut writer, SUBTITLE, &[], &ch.description)?;
+            //newest episode rather than the render time, so unchanged feeds hash the same
+            let updated = match episodes.iter().map(|ep| ep.published).max() {
+                Some(t) => t.to_rfc3339(),
+                None => Utc::now().to_rfc3339(),
+            };
+            text(&mut writer, UPDATED, &[], &updated)?;
+            empty(&mut writer, LINK, &[("rel", ALTERNATE), ("href", &ch.link)])?;
+            author(&mut writer, ch)?;
+            let mut cats = ch.itunes_categories();
+            cats.reverse();
+            while let Some(cat) = cats.pop() {
+                empty(&mut writer, CATEGORY, &[(TERM, &cat.text)])?;
+                cats.extend(cat.subcategories.into_iter().rev());
+            }
+            text(&mut writer, LOGO, &[], &ch.image)?;
+            empty(&mut writer, ITUNES_IMAGE, &[("href", &ch.image)])?;
+
+            for ep in episodes {
+                self.write_entry(&mut writer, ch, ep)?;
+            }
+
+            end(&mut writer, FEED)?;
+            writer.write_event(Event::Eof)?;
+            Ok(())
+        }
+
+        fn parse(&self, doc: &str) -> Result<Vec<Episode>, Error> {
+            read_items(doc, FEED, ENTRY)?
+                .iter()
+                .map(|fields| self.read_entry(fields))
+                .collect()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::Client;
+        use crate::model::{api::Response, Channel, Episode};
+        use crate::se::{Format, Serializer};
+        use std::error::Error;
+        use std::{fs, io};
+
+        #[test]
+        fn atom_roundtrip() -> Result<(), Box<dyn Error>> {
+            let atom = Client;
+            let ch = Channel {
+                title: "test podcast".to_string(),
+                author: "John Doe".to_string(),
+                link: "https://example.com/".to_string(),
+                language: "zh-cn".to_string(),
+                media_base_url: "https://example.com/media/".to_string(),
+                web_base_url: "https://example.com/radios/".to_string(),
+                image: "https://example.com/podcast-icon.jpg".to_string(),
+                ..Default::default()
+            };
+            let json: String = fs::read_to_string("api_response.json")?;
+            let response: Response = serde_json::from_str(&json)?;
+            let episodes = Episode::from_response(&response, &ch)?;
+            let xml_str = atom.render(&ch, &episodes)?;
+            assert!(xml_str.contains(r#"<feed xmlns="http://www.w3.org/2005/Atom""#));
+            assert!(xml_str.contains("<updated>2021-07-27T22:00:00+08:00</updated>"));
+            assert!(xml_str.contains(
+                r#"<link rel="enclosure" type="audio/mpeg" href="https://example.com/media/5d499753-a4d1-409e-9a1a-d6bb0e23e7a1.mp3" length="0"/>"#
+            ));
+            assert_eq!(xml_str.matches("<name>John Doe</name>").count(), 4);
+            assert_eq!(atom.parse(&xml_str)?, episodes);
+            Ok(())
+        }
+
+        #[test]
+        fn format_mismatch() -> Result<(), Box<dyn Error>> {
+            let ch = Channel {
+                image: "https://example.com/podcast-icon.jpg".to_string(),
+                ..Default::default()
+            };
+            let json: String = fs::read_to_string("api_response.json")?;
+            let response: Response = serde_json::from_str(&json)?;
+            let episodes = Episode::from_response(&response, &ch)?;
+            let rss = Format::Rss.serializer();
+            let atom = Format::Atom.serializer();
+            let json = Format::Json.serializer();
+            let docs = [
+                rss.render(&ch, &episodes)?,
+                atom.render(&ch, &episodes)?,
+                json.render(&ch, &episodes)?,
+            ];
+            //a feed is only read back by the serializer that wrote it
+            for (i, parser) in [&rss, &atom, &json].iter().enumerate() {
+                for (j, doc) in docs.iter().enumerate() {
+                    match parser.parse(doc) {
+                        Ok(parsed) => {
+                            assert_eq!(i, j);
+                            assert_eq!(parsed, episodes);
+                        }
+                        Err(err) => {
+                            assert_ne!(i, j);
+                            assert!(matches!(err, crate::error::Error::Decode(_)), "{}", err);
+                            assert!(err.to_string().contains("param.format"), "{}", err);
+                        }
+                    }
+                }
+            }
+            assert!(rss.parse("").is_err());
+            Ok(())
+        }
+
+        //sink that takes `left` bytes and then fails, like a full disk
+        struct Full {
+            data: Vec<u8>,
+            left: usize,
+        }
+
+        impl io::Write for Full {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.left == 0 {
+                    return Err(io::Error::other("sink full"));
+                }
+                let n = buf.len().min(self.left);
+                self.data.extend_from_slice(&buf[..n]);
+                self.left -= n;
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn write_to_sink() -> Result<(), Box<dyn Error>> {
+            let ch = Channel::default();
+            let json: String = fs::read_to_string("api_response.json")?;
+            let response: Response = serde_json::from_str(&json)?;
+            let episodes = Episode::from_response(&response, &ch)?;
+            let xml_str = Client.render(&ch, &episodes)?;
+
+            let mut sink = Full {
+                data: vec![],
+                left: usize::MAX,
+            };
+            Client.write(&ch, &episodes, &mut sink)?;
+            assert_eq!(String::from_utf8(sink.data)?, xml_str);
+
+            let mut full = Full {
+                data: vec![],
+                left: 100,
+            };
+            let err = Client.write(&ch, &episodes, &mut full).unwrap_err();
+            //a failing sink is a storage error, not a broken document
+            assert!(matches!(err, crate::error::Error::Storage(_)), "{}", err);
+            assert_eq!(full.data, xml_str.as_bytes()[..100].to_vec());
+            Ok(())
+        }
+    }
+}
+
+pub mod json {
+    use crate::error::Error;
+    use crate::model::{Channel, Episode};
+    use chrono::DateTime;
+    use serde::{Deserialize, Serialize};
+    use std::io::Write;
+
+    const VERSION: &str = "https://jsonfeed.org/version/1.1";
+    const MPEG: &str = "audio/mpeg";
+
+    //JSON Feed 1.1, https://www.jsonfeed.org/version/1.1/
+    //urls that are not set are left out, an empty string is not a valid url
+    pub struct Client;
+
+    #[derive(Serialize, Deserialize)]
+    struct Feed {
+        version: String,
+        title: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        home_page_url: String,
+        #[serde(default)]
+        description: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        icon: String,
+        #[serde(default)]
+        authors: Vec<Author>,
+        #[serde(default)]
+        language: String,
+        items: Vec<Item>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Author {
+        name: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Item {
+        id: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        url: String,
+        #[serde(default)]
+        title: String,
+        #[serde(default)]
+        content_html: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        image: String,
+        date_published: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+        #[serde(default)]
+        attachments: Vec<Attachment>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Attachment {
+        url: String,
+        mime_type: String,
+        //the api does not report sizes, left out rather than claiming 0
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size_in_bytes: Option<u64>,
+        #[serde(default)]
+        duration_in_seconds: u32,
+    }
+
+    impl From<&Episode> for Item {
+        fn from(ep: &Episode) -> Self {
+            Item {
+                id: ep.guid.clone(),
+                url: ep.link.clone(),
+                title: ep.title.clone(),
+                content_html: ep.description.clone(),
+                image: ep.image.clone(),
+                date_published: ep.published.to_rfc3339(),
+                tags: ep.categories.clone(),
+                attachments: vec![Attachment {
+                    url: ep.audio_url.clone(),
+                    mime_type: MPEG.to_string(),
+                    size_in_bytes: Some(ep.length).filter(|&n| n > 0),
+                    duration_in_seconds: ep.duration,
+                }],
+            }
+        }
+    }
+
+    impl Item {
+        fn into_episode(self) -> Result<Episode, Error> {
+            let published = DateTime::parse_from_rfc3339(&self.date_published)?;
+            let attachment = self.attachments.into_iter().next();
+            Ok(Episode {
+                guid: self.id,
+                title: self.title,
+                description: self.content_html,
+                link: self.url,
+                audio_url: attachment
+                    .as_ref()
+                    .map(|a| a.url.clone())
+                    .unwrap_or_default(),
+                length: attachment
+                    .as_ref()
+                    .and_then(|a| a.size_in_bytes)
+                    .unwrap_or(0),
+                duration: attachment.map(|a| a.duration_in_seconds).unwrap_or(0),
+                image: self.image,
+                published,
+                transcript: None,
+                chapters: None,
+                persons: vec![],
+                categories: self.tags,
+            })
+        }
+    }
+
+    impl super::Serializer for Client {
+        fn write(
+            &self,
+            ch: &Channel,
+            episodes: &[Episode],
+            out: &mut dyn Write,
+        ) -> Result<(), Error> {
+            let feed = Feed {
+                version: VERSION.to_string(),
+                title: ch.title.clone(),
+                home_page_url: ch.link.clone(),
+                description: ch.description.clone(),
+                icon: ch.image.clone(),
+                authors: vec![Author {
+                    name: ch.author.clone(),
+                }],
+                language: ch.language.clone(),
+                items: episodes.iter().map(Item::from).collect(),
+            };
+            serde_json::to_writer_pretty(out, &feed).map_err(|e| match e.is_io() {
+                true => Error::storage(e),
+                false => Error::Xml(e.into()),
+            })
+        }
+
+        fn parse(&self, doc: &str) -> Result<Vec<Episode>, Error> {
+            if doc.trim_start().starts_with('<') {
+                return Err(Error::decode(
+                    "stored feed is xml, expected a json feed; was param.format changed?",
+                ));
+            }
+            let feed: Feed = serde_json::from_str(doc)?;
+            feed.items.into_iter().map(Item::into_episode).collect()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::Client;
+        use crate::model::{api::Response, Channel, Episode};
+        use crate::se::{itune, Serializer};
+        use std::error::Error;
+        use std::fs;
+
+        #[test]
+        fn json_agrees_with_rss() -> Result<(), Box<dyn Error>> {
+            let ch = Channel {
+                title: "test podcast".to_string(),
+                media_base_url: "https://example.com/media/".to_string(),
+                web_base_url: "https://example.com/radios/".to_string(),
+                image_base_url: "https://example.com/img/".to_string(),
+                image: "https://example.com/podcast-icon.jpg".to_string(),
+                ..Default::default()
+            };
+            let json: String = fs::read_to_string("api_response.json")?;
+            let response: Response = serde_json::from_str(&json)?;
+            let feed = Client.to_xml(&ch, &response)?;
+
+            let value: serde_json::Value = serde_json::from_str(&feed)?;
+            assert_eq!(value["version"], "https://jsonfeed.org/version/1.1");
+            let item = &value["items"][0];
+            assert_eq!(item["date_published"], "2021-07-27T22:00:00+08:00");
+            assert_eq!(
+                item["image"],
+                "https://example.com/img/fe15d545-8434-497d-82de-4081ea5036cd.jpg"
+            );
+            assert_eq!(item["attachments"][0]["mime_type"], "audio/mpeg");
+            assert_eq!(item["attachments"][0]["duration_in_seconds"], 2830);
+            assert!(item["attachments"][0].get("size_in_bytes").is_none());
+            //the channel has no link
+            assert!(value.get("home_page_url").is_none());
+            assert_eq!(value["icon"], "https://example.com/podcast-icon.jpg");
+
+            let rss = itune::Client::default();
+            let episodes = Episode::from_response(&response, &ch)?;
+            assert_eq!(Client.parse(&feed)?, episodes);
+            assert_eq!(rss.parse(&rss.to_xml(&ch, &response)?)?, episodes);
+            Ok(())
+        }
+
+        #[test]
+        fn sink_error() {
+            let mut buf = [0u8; 16];
+            let mut sink: &mut [u8] = &mut buf;
+            let err = Client
+                .write(&Channel::default(), &[], &mut sink)
+                .unwrap_err();
+            assert!(matches!(err, crate::error::Error::Storage(_)), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//aliyun oss request signatures, V1 (HMAC-SHA1) and V4 (OSS4-HMAC-SHA256)
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+const V4_ALGORITHM: &str = "OSS4-HMAC-SHA256";
+const V4_REQUEST: &str = "aliyun_v4_request";
+//the body is not hashed, content-md5 covers it
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+pub const OSS_DATE: &str = "x-oss-date";
+pub const OSS_CONTENT_SHA256: &str = "x-oss-content-sha256";
+
+//the parts of a request a signature covers
+pub struct Request<'a> {
+    pub verb: &'a str,
+    pub bucket: &'a str,
+    pub key: &'a str,
+    pub content_md5: &'a str,
+    pub content_type: &'a str,
+    //x-oss-* headers sent with the request
+    pub oss_headers: &'a [(String, String)],
+    //query parameters, signed by V4 only
+    pub query: &'a [(String, String)],
+}
+
+//Content-MD5 header, base64 of the md5 digest
+pub fn content_md5(body: &[u8]) -> String {
+    base64::encode(Md5::digest(body))
+}
+
+//Date header, rfc 1123 in GMT
+pub fn http_date(t: DateTime<Utc>) -> String {
+    t.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+//x-oss-date header of V4
+pub fn timestamp(t: DateTime<Utc>) -> String {
+    t.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+//lowercase x-oss-* headers sorted by name, "name:value\n" each
+fn oss_headers(req: &Request) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = req
+        .oss_headers
+        .iter()
+        .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+        .filter(|(k, _)| k.starts_with("x-oss-"))
+        .collect();
+    headers.sort();
+    headers
+}
+
+fn hmac_sha1(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("hmac takes any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac takes any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+//Authorization header of V1, `date` is the Date header sent
+pub fn v1(req: &Request, id: &str, secret: &str, date: &str) -> String {
+    let mut to_sign = format!(
+        "{}\n{}\n{}\n{}\n",
+        req.verb, req.content_md5, req.content_type, date
+    );
+    for (k, v) in oss_headers(req) {
+        to_sign.push_str(&format!("{}:{}\n", k, v));
+    }
+    to_sign.push_str(&format!("/{}/{}", req.bucket, req.key));
+
+    let signature = base64::encode(hmac_sha1(secret.as_bytes(), &to_sign));
+    format!("OSS {}:{}", id, signature)
+}
+
+//rfc 3986 percent encoding, '/' kept in paths
+fn uri_encode(s: &str, keep_slash: bool) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b'/' if keep_slash => "/".to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+//Authorization header of V4, the request must carry x-oss-date of `now`
+//and x-oss-content-sha256 as sent
+pub fn v4(req: &Request, id: &str, secret: &str, region: &str, now: DateTime<Utc>) -> String {
+    let date = now.format("%Y%m%d").to_string();
+    let scope = format!("{}/{}/oss/{}", date, region, V4_REQUEST);
+
+    let mut headers = oss_headers(req);
+    if !req.content_md5.is_empty() {
+        headers.push(("content-md5".to_string(), req.content_md5.to_string()));
+    }
+    if !req.content_type.is_empty() {
+        headers.push(("content-type".to_string(), req.content_type.to_string()));
+    }
+    headers.sort();
+    let payload = headers
+        .iter()
+        .find(|(k, _)| k == OSS_CONTENT_SHA256)
+        .map(|(_, v)| v.as_str())
+        .unwrap_or(UNSIGNED_PAYLOAD);
+
+    let mut query: Vec<(String, String)> = req
+        .query
+        .iter()
+        .map(|(k, v)| (uri_encode(k, false), uri_encode(v, false)))
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(k, v)| match v.is_empty() {
+            true => k.clone(),
+            false => format!("{}={}", k, v),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v))
+        .collect();
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        req.verb,
+        uri_encode(&format!("/{}/{}", req.bucket, req.key), true),
+        canonical_query,
+        canonical_headers,
+        //additional headers
+        "",
+        payload
+    );
+    let to_sign = format!(
+        "{}\n{}\n{}\n{}",
+        V4_ALGORITHM,
+        timestamp(now),
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = [date.as_str(), region, "oss", V4_REQUEST]
+        .iter()
+        .fold(format!("aliyun_v4{}", secret).into_bytes(), |key, part| {
+            hmac_sha256(&key, part)
+        });
+    let signature = hex::encode(hmac_sha256(&key, &to_sign));
+    format!(
+        "{} Credential={}/{},Signature={}",
+        V4_ALGORITHM, id, scope, signature
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_md5, http_date, timestamp, v1, v4, Request, OSS_CONTENT_SHA256, OSS_DATE};
+    use chrono::{TimeZone, Utc};
+
+    //"在Header中包含签名" example of the oss documentation
+    #[test]
+    fn v1_documented() {
+        let headers = vec![
+            ("X-OSS-Meta-Author".to_string(), "foo@bar.com".to_string()),
+            ("X-OSS-Magic".to_string(), "abracadabra".to_string()),
+        ];
+        let req = Request {
+            verb: "PUT",
+            bucket: "oss-example",
+            key: "nelson",
+            content_md5: "ODBGOERFMDMzQTczRUY3NUE3NzA5QzdFNUYzMDQxNEM=",
+            content_type: "text/html",
+            oss_headers: &headers,
+            query: &[],
+        };
+        let date = http_date(Utc.ymd(2005, 11, 17).and_hms(18, 49, 58));
+        assert_eq!(date, "Thu, 17 Nov 2005 18:49:58 GMT");
+        assert_eq!(
+            v1(
+                &req,
+                "44CF9590006BF252F707",
+                "OtxrzxIsfpFjA7SwPzILwy8Bw21TLhquhboDYROV",
+                &date
+            ),
+            "OSS 44CF9590006BF252F707:26NBxoKdsyly4EDv6inkoDft/yA="
+        );
+    }
+
+    #[test]
+    fn md5_header() {
+        assert_eq!(content_md5(b""), "1B2M2Y8AsgTpgAmY7PhCfg==");
+        assert_eq!(content_md5(b"<rss/>"), "Xxo1V4KBJ4196LelQk/tDg==");
+    }
+
+    //TestAuthHeader of aliyun's oss go sdk v2 signer, query and non oss headers included
+    #[test]
+    fn v4_sdk_vector() {
+        let now = Utc.timestamp(1702743657, 0);
+        let mut headers: Vec<(String, String)> = [
+            ("x-oss-head1", "value"),
+            ("abc", "value"),
+            ("ZAbc", "value"),
+            ("XYZ", "value"),
+            (OSS_CONTENT_SHA256, "UNSIGNED-PAYLOAD"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        headers.push((OSS_DATE.to_string(), timestamp(now)));
+        let query: Vec<(String, String)> = [
+            ("param1", "value1"),
+            ("+param1", "value3"),
+            ("|param1", "value4"),
+            ("+param2", ""),
+            ("|param2", ""),
+            ("param2", ""),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let req = Request {
+            verb: "PUT",
+            bucket: "bucket",
+            key: "1234+-/123/1.txt",
+            content_md5: "",
+            content_type: "text/plain",
+            oss_headers: &headers,
+            query: &query,
+        };
+        assert_eq!(timestamp(now), "20231216T162057Z");
+        assert_eq!(
+            v4(&req, "ak", "sk", "cn-hangzhou", now),
+            concat!(
+                "OSS4-HMAC-SHA256 Credential=ak/20231216/cn-hangzhou/oss/aliyun_v4_request,",
+                "Signature=e21d18daa82167720f9b1047ae7e7f1ce7cb77a31e8203a7d5f4624fa0284afe"
+            )
+        );
+    }
+}
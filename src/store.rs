@@ -0,0 +1,1100 @@
+use crate::error::Error;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+pub use local::LocalParam;
+pub use oss::{OssParam, Signature, Sts};
+pub use s3::S3Param;
+
+//metadata key holding se::content_hash of the stored feed
+pub const HASH_META: &str = "feed-hash";
+
+//where a generated feed is written
+pub trait FeedStore: Send + Sync {
+    //write the feed along with its content hash,
+    //`content_type` of the feed format unless the param sets one
+    fn put(&self, body: Vec<u8>, hash: &str, content_type: &str) -> Result<(), Error>;
+
+    //the stored feed, None when nothing was published yet
+    fn get(&self) -> Result<Option<Vec<u8>>, Error>;
+
+    //hash saved by the last `put`, None when absent
+    fn hash(&self) -> Result<Option<String>, Error>;
+
+    //the hash to `put` with a rendered feed of `content_hash`,
+    //settings that change the stored objects are part of it
+    fn hash_of(&self, content_hash: &str) -> String {
+        content_hash.to_string()
+    }
+}
+
+//`storage_param` of an event, the backend is picked by its `service` field
+#[derive(Deserialize, Debug)]
+#[serde(tag = "service", rename_all = "lowercase")]
+pub enum StorageParam {
+    S3(S3Param),
+    Oss(OssParam),
+    Local(LocalParam),
+}
+
+//content coding of an uploaded feed, podcast clients send `Accept-Encoding: gzip, br`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Gzip,
+    Br,
+}
+
+impl Encoding {
+    //the Content-Encoding value
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Br => "br",
+        }
+    }
+
+    pub fn encode(self, body: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Encoding::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(vec![], flate2::Compression::best());
+                encoder.write_all(body).map_err(Error::storage)?;
+                encoder.finish().map_err(Error::storage)
+            }
+            Encoding::Br => {
+                //quality 11, 4MB window; unlike CompressorWriter, which finishes the
+                //stream on drop, this reports a failure to finish it
+                let params = brotli::enc::BrotliEncoderParams {
+                    quality: 11,
+                    lgwin: 22,
+                    ..Default::default()
+                };
+                let mut data = vec![];
+                brotli::BrotliCompress(&mut &body[..], &mut data, &params)
+                    .map_err(Error::storage)?;
+                Ok(data)
+            }
+        }
+    }
+
+    fn decode(self, body: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut data = vec![];
+        match self {
+            Encoding::Gzip => flate2::read::GzDecoder::new(body).read_to_end(&mut data),
+            Encoding::Br => brotli::Decompressor::new(body, 4096).read_to_end(&mut data),
+        }
+        .map_err(Error::storage)?;
+        Ok(data)
+    }
+}
+
+//a stored body back in plain form, by the Content-Encoding it was served with
+fn decode(content_encoding: Option<&str>, body: Vec<u8>) -> Result<Vec<u8>, Error> {
+    match content_encoding.map(str::trim) {
+        Some("gzip") => Encoding::Gzip.decode(&body),
+        Some("br") => Encoding::Br.decode(&body),
+        Some("") | Some("identity") | None => Ok(body),
+        Some(other) => Err(Error::storage(format!(
+            "stored feed has unknown encoding {}",
+            other
+        ))),
+    }
+}
+
+//what a s3 or oss `put` sends besides the body
+#[derive(Default)]
+struct PutOptions<'a> {
+    encoding: Option<Encoding>,
+    compressed_key: Option<&'a str>,
+    acl: Option<&'a str>,
+    content_type: Option<&'a str>,
+    cache_control: Option<&'a str>,
+    content_disposition: Option<&'a str>,
+    storage_class: Option<&'a str>,
+    metadata: Option<&'a HashMap<String, String>>,
+}
+
+impl PutOptions<'_> {
+    //`FeedStore::hash_of` for s3 and oss, changing any option changes the hash
+    //so an unchanged feed is uploaded once more with the new settings
+    fn hash(&self, content_hash: &str) -> String {
+        let named = [
+            ("encoding", self.encoding.map(Encoding::name)),
+            //the copy only exists when compressing
+            (
+                "compressed_key",
+                self.compressed_key.filter(|_| self.encoding.is_some()),
+            ),
+            ("acl", self.acl),
+            ("content_type", self.content_type),
+            ("cache_control", self.cache_control),
+            ("content_disposition", self.content_disposition),
+            ("storage_class", self.storage_class),
+        ];
+        let mut fields: Vec<(String, &str)> = named
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), (*value)?)))
+            .collect();
+        let mut metadata: Vec<_> = self.metadata.into_iter().flatten().collect();
+        metadata.sort();
+        for (k, v) in metadata {
+            fields.push((format!("metadata.{}", k), v));
+        }
+        if fields.is_empty() {
+            return content_hash.to_string();
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(content_hash);
+        for (name, value) in fields {
+            hasher.update(format!("\n{}={}", name, value));
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+//one object written by a `put`
+struct Object {
+    key: String,
+    body: Vec<u8>,
+    encoding: Option<Encoding>,
+}
+
+//`key` compressed in place, or left plain next to a compressed copy at `compressed_key`.
+//the copy goes first, so a failed upload leaves the hash of `key` stale and is retried
+fn objects(
+    key: &str,
+    body: Vec<u8>,
+    encoding: Option<Encoding>,
+    compressed_key: Option<&str>,
+) -> Result<Vec<Object>, Error> {
+    let encoding = match encoding {
+        Some(encoding) => encoding,
+        None => {
+            return Ok(vec![Object {
+                key: key.to_string(),
+                body,
+                encoding: None,
+            }])
+        }
+    };
+    let compressed = encoding.encode(&body)?;
+    let mut objects = vec![Object {
+        key: compressed_key.unwrap_or(key).to_string(),
+        body: compressed,
+        encoding: Some(encoding),
+    }];
+    if compressed_key.is_some() {
+        objects.push(Object {
+            key: key.to_string(),
+            body,
+            encoding: None,
+        });
+    }
+    Ok(objects)
+}
+
+//oss credentials fall back to the function environment when `sts` is absent
+pub fn open(param: StorageParam, sts: Option<Sts>) -> Result<Arc<dyn FeedStore>, Error> {
+    let store: Arc<dyn FeedStore> = match param {
+        StorageParam::S3(param) => Arc::new(s3::Store::new(param)?),
+        StorageParam::Oss(param) => {
+            let sts = match sts {
+                Some(sts) => sts,
+                None => Sts::from_env()?,
+            };
+            Arc::new(oss::Store::new(param, sts)?)
+        }
+        StorageParam::Local(param) => Arc::new(local::Store::new(param)),
+    };
+    Ok(store)
+}
+
+pub mod s3 {
+    use super::{decode, objects, Encoding, FeedStore, Object, PutOptions, HASH_META};
+    use crate::error::Error;
+    use rusoto_core::{ByteStream, Region, RusotoError};
+    use rusoto_s3::{
+        GetObjectError, GetObjectRequest, HeadObjectError, HeadObjectRequest, PutObjectRequest,
+        S3Client, S3,
+    };
+    use serde::Deserialize;
+    use std::collections::HashMap;
+    use std::io::Read;
+
+    #[derive(Deserialize, Debug)]
+    pub struct S3Param {
+        pub bucket: String,
+        pub key: String,
+        //region name such as ap-northeast-1, us-east-1 when absent
+        pub region: Option<String>,
+        //s3 compatible service such as minio or r2, e.g. http://127.0.0.1:9000,
+        //buckets are addressed path-style as endpoint/bucket/key
+        pub endpoint: Option<String>,
+        pub acl: Option<String>,
+        pub content_type: Option<String>,
+        //compress the feed, in place or as a copy at `compressed_key`
+        pub encoding: Option<Encoding>,
+        pub compressed_key: Option<String>,
+        pub cache_control: Option<String>,
+        pub content_disposition: Option<String>,
+        //STANDARD, STANDARD_IA, INTELLIGENT_TIERING ..
+        pub storage_class: Option<String>,
+        //user metadata, sent as x-amz-meta-*
+        #[serde(default)]
+        pub metadata: HashMap<String, String>,
+    }
+
+    pub struct Store {
+        param: S3Param,
+        client: S3Client,
+    }
+
+    impl Store {
+        pub fn new(param: S3Param) -> Result<Self, Error> {
+            let region = region(&param)?;
+            Ok(Store {
+                param,
+                client: S3Client::new(region),
+            })
+        }
+    }
+
+    //a custom endpoint signs with `region`, r2 expects "auto"
+    pub(super) fn region(param: &S3Param) -> Result<Region, Error> {
+        match (&param.endpoint, &param.region) {
+            (Some(endpoint), region) => Ok(Region::Custom {
+                name: region.clone().unwrap_or_else(|| "us-east-1".to_string()),
+                endpoint: endpoint.trim_end_matches('/').to_string(),
+            }),
+            (None, Some(name)) => name
+                .parse()
+                .map_err(|e| Error::config(format!("s3 region {}: {}", name, e))),
+            (None, None) => Ok(Region::UsEast1),
+        }
+    }
+
+    impl Store {
+        //the request that writes one object of a `put`
+        pub(super) fn put_request(
+            &self,
+            object: Object,
+            hash: &str,
+            content_type: &str,
+        ) -> PutObjectRequest {
+            let S3Param {
+                acl,
+                bucket,
+                cache_control,
+                content_disposition,
+                storage_class,
+                metadata,
+                ..
+            } = &self.param;
+            let mut metadata = metadata.clone();
+            metadata.insert(HASH_META.to_string(), hash.to_string());
+            PutObjectRequest {
+                acl: acl.clone(),
+                body: Some(ByteStream::from(object.body)),
+                bucket: bucket.clone(),
+                key: object.key,
+                content_type: Some(
+                    self.param
+                        .content_type
+                        .clone()
+                        .unwrap_or_else(|| content_type.to_string()),
+                ),
+                content_encoding: object.encoding.map(|e| e.name().to_string()),
+                cache_control: cache_control.clone(),
+                content_disposition: content_disposition.clone(),
+                storage_class: storage_class.clone(),
+                metadata: Some(metadata),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl FeedStore for Store {
+        fn put(&self, body: Vec<u8>, hash: &str, content_type: &str) -> Result<(), Error> {
+            let S3Param {
+                key,
+                encoding,
+                compressed_key,
+                ..
+            } = &self.param;
+            for object in objects(key, body, *encoding, compressed_key.as_deref())? {
+                self.client
+                    .put_object(self.put_request(object, hash, content_type))
+                    .sync()
+                    .map_err(Error::storage)?;
+            }
+            Ok(())
+        }
+
+        fn get(&self) -> Result<Option<Vec<u8>>, Error> {
+            let output = self
+                .client
+                .get_object(GetObjectRequest {
+                    bucket: self.param.bucket.clone(),
+                    key: self.param.key.clone(),
+                    ..Default::default()
+                })
+                .sync();
+            let output = match output {
+                Ok(output) => output,
+                Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => return Ok(None),
+                Err(e) => return Err(Error::storage(e)),
+            };
+            let mut body = vec![];
+            if let Some(stream) = output.body {
+                stream
+                    .into_blocking_read()
+                    .read_to_end(&mut body)
+                    .map_err(Error::storage)?;
+            }
+            decode(output.content_encoding.as_deref(), body).map(Some)
+        }
+
+        fn hash(&self) -> Result<Option<String>, Error> {
+            let output = self
+                .client
+                .head_object(HeadObjectRequest {
+                    bucket: self.param.bucket.clone(),
+                    key: self.param.key.clone(),
+                    ..Default::default()
+                })
+                .sync();
+            match output {
+                Ok(output) => Ok(output.metadata.and_then(|mut m| m.remove(HASH_META))),
+                //head responses carry no error body to tell NoSuchKey apart
+                Err(RusotoError::Service(HeadObjectError::NoSuchKey(_))) => Ok(None),
+                Err(RusotoError::Unknown(ref resp)) if resp.status.as_u16() == 404 => Ok(None),
+                Err(e) => Err(Error::storage(e)),
+            }
+        }
+
+        fn hash_of(&self, content_hash: &str) -> String {
+            let param = &self.param;
+            PutOptions {
+                encoding: param.encoding,
+                compressed_key: param.compressed_key.as_deref(),
+                acl: param.acl.as_deref(),
+                content_type: param.content_type.as_deref(),
+                cache_control: param.cache_control.as_deref(),
+                content_disposition: param.content_disposition.as_deref(),
+                storage_class: param.storage_class.as_deref(),
+                metadata: Some(&param.metadata),
+            }
+            .hash(content_hash)
+        }
+    }
+}
+
+pub mod oss {
+    use super::{decode, objects, Encoding, FeedStore, Object, PutOptions, HASH_META};
+    use crate::error::Error;
+    use crate::sign;
+    use chrono::Utc;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+    use std::env;
+    use std::io::Read;
+
+    #[derive(Deserialize, Debug)]
+    pub struct OssParam {
+        pub bucket: String,
+        pub key: String,
+        //public-read when absent, `default` inherits the bucket acl
+        pub acl: Option<String>,
+        pub content_type: Option<String>,
+        //region endpoint such as oss-cn-shanghai.aliyuncs.com
+        pub endpoint: String,
+        #[serde(default)]
+        pub signature: Signature,
+        //cn-shanghai .. signed by v4, taken from `endpoint` when absent
+        pub region: Option<String>,
+        //plain http suits the -internal endpoints inside function compute
+        #[serde(default)]
+        pub https: bool,
+        //compress the feed, in place or as a copy at `compressed_key`
+        pub encoding: Option<Encoding>,
+        pub compressed_key: Option<String>,
+        pub cache_control: Option<String>,
+        pub content_disposition: Option<String>,
+        //Standard, IA, Archive ..
+        pub storage_class: Option<String>,
+        //user metadata, sent as x-oss-meta-*
+        #[serde(default)]
+        pub metadata: HashMap<String, String>,
+    }
+
+    #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Signature {
+        //HMAC-SHA1 of the request, accepted by every endpoint
+        #[default]
+        V1,
+        //OSS4-HMAC-SHA256 scoped to the region
+        V4,
+    }
+
+    //temporary credentials handed to the function
+    #[derive(Clone)]
+    pub struct Sts {
+        pub id: String,
+        pub secret: String,
+        pub token: String,
+    }
+
+    impl Sts {
+        //variables set by function compute for the service role
+        pub fn from_env() -> Result<Self, Error> {
+            let var = |name: &str| {
+                env::var(name).map_err(|_| Error::config(format!("{} is not set", name)))
+            };
+            Ok(Sts {
+                id: var("ALIBABA_CLOUD_ACCESS_KEY_ID")?,
+                secret: var("ALIBABA_CLOUD_ACCESS_KEY_SECRET")?,
+                token: var("ALIBABA_CLOUD_SECURITY_TOKEN")?,
+            })
+        }
+    }
+
+    pub struct Store {
+        param: OssParam,
+        sts: Sts,
+        //scope of v4 signatures
+        region: String,
+    }
+
+    impl Store {
+        pub fn new(param: OssParam, sts: Sts) -> Result<Self, Error> {
+            let region = match (&param.region, param.signature) {
+                (Some(region), _) => region.clone(),
+                (None, Signature::V4) => region(&param.endpoint).ok_or_else(|| {
+                    Error::config(format!(
+                        "oss region is not set and endpoint {} names none",
+                        param.endpoint
+                    ))
+                })?,
+                (None, Signature::V1) => String::new(),
+            };
+            Ok(Store { param, sts, region })
+        }
+    }
+
+    //oss-cn-shanghai-internal.aliyuncs.com is cn-shanghai, custom domains name none
+    fn region(endpoint: &str) -> Option<String> {
+        let host = endpoint.strip_suffix(".aliyuncs.com")?;
+        let region = host.strip_prefix("oss-")?;
+        let region = region.strip_suffix("-internal").unwrap_or(region);
+        //global acceleration endpoints are not regions
+        if region.is_empty() || region.contains('.') || region.starts_with("accelerate") {
+            return None;
+        }
+        Some(region.to_string())
+    }
+
+    impl Store {
+        fn url(&self, key: &str) -> String {
+            let OssParam {
+                endpoint,
+                bucket,
+                https,
+                ..
+            } = &self.param;
+            let scheme = if *https { "https" } else { "http" };
+            format!("{}://{}.{}/{}", scheme, bucket, endpoint, key)
+        }
+
+        //signed request for `key` carrying the security token and `oss_headers`
+        fn request(
+            &self,
+            verb: &str,
+            key: &str,
+            content_type: &str,
+            content_md5: &str,
+            oss_headers: Vec<(String, String)>,
+        ) -> ureq::Request {
+            let OssParam {
+                endpoint, bucket, ..
+            } = &self.param;
+            let Sts { id, secret, token } = &self.sts;
+
+            let now = Utc::now();
+            let format_date = sign::http_date(now);
+
+            let secret_header = ("x-oss-security-token".to_string(), token.clone());
+            let mut headers = vec![secret_header];
+            headers.extend(oss_headers);
+            if self.param.signature == Signature::V4 {
+                headers.push((sign::OSS_DATE.to_string(), sign::timestamp(now)));
+                headers.push((
+                    sign::OSS_CONTENT_SHA256.to_string(),
+                    sign::UNSIGNED_PAYLOAD.to_string(),
+                ));
+            }
+
+            let signed = sign::Request {
+                verb,
+                bucket,
+                key,
+                content_md5,
+                content_type,
+                oss_headers: &headers,
+                query: &[],
+            };
+            let auth = match self.param.signature {
+                Signature::V1 => sign::v1(&signed, id, secret, &format_date),
+                Signature::V4 => sign::v4(&signed, id, secret, &self.region, now),
+            };
+
+            let mut req = ureq::request(verb, &self.url(key))
+                .set("authorization", &auth)
+                .set("Host", &format!("{}.{}", bucket, endpoint))
+                .set("date", &format_date);
+            if !content_type.is_empty() {
+                req = req.set("Content-Type", content_type);
+            }
+            if !content_md5.is_empty() {
+                req = req.set("Content-MD5", content_md5);
+            }
+            for (k, v) in headers.iter() {
+                req = req.set(k, v);
+            }
+            req
+        }
+    }
+
+    impl Store {
+        //the signed request that writes one object of a `put`, the body is sent by the caller
+        pub(super) fn put_request(
+            &self,
+            object: &Object,
+            hash: &str,
+            content_type: &str,
+        ) -> ureq::Request {
+            let OssParam {
+                acl,
+                cache_control,
+                content_disposition,
+                storage_class,
+                metadata,
+                ..
+            } = &self.param;
+            let acl1 = acl.clone().unwrap_or_else(|| "public-read".to_string());
+            let content_type = self.param.content_type.as_deref().unwrap_or(content_type);
+
+            let x_oss_acl = "x-oss-object-acl";
+
+            //x-oss-* headers are signed, the others are not
+            let mut oss_headers = vec![(x_oss_acl.to_string(), acl1)];
+            if let Some(class) = storage_class {
+                oss_headers.push(("x-oss-storage-class".to_string(), class.clone()));
+            }
+            for (k, v) in metadata
+                .iter()
+                .filter(|(k, _)| !k.eq_ignore_ascii_case(HASH_META))
+            {
+                oss_headers.push((format!("x-oss-meta-{}", k.to_lowercase()), v.clone()));
+            }
+            oss_headers.push((format!("x-oss-meta-{}", HASH_META), hash.to_string()));
+
+            let body_md5 = sign::content_md5(&object.body);
+            let mut req = self.request("PUT", &object.key, content_type, &body_md5, oss_headers);
+            if let Some(encoding) = object.encoding {
+                req = req.set("Content-Encoding", encoding.name());
+            }
+            if let Some(cache_control) = cache_control {
+                req = req.set("Cache-Control", cache_control);
+            }
+            if let Some(disposition) = content_disposition {
+                req = req.set("Content-Disposition", disposition);
+            }
+            req
+        }
+    }
+
+    impl FeedStore for Store {
+        fn put(&self, body: Vec<u8>, hash: &str, content_type: &str) -> Result<(), Error> {
+            let OssParam {
+                key,
+                encoding,
+                compressed_key,
+                ..
+            } = &self.param;
+            for object in objects(key, body, *encoding, compressed_key.as_deref())? {
+                self.put_request(&object, hash, content_type)
+                    .send_bytes(&object.body)
+                    .map_err(|e| Error::storage(e.to_string()))?;
+            }
+            Ok(())
+        }
+
+        fn get(&self) -> Result<Option<Vec<u8>>, Error> {
+            let resp = match self.request("GET", &self.param.key, "", "", vec![]).call() {
+                Ok(resp) => resp,
+                Err(ureq::Error::Status(404, _)) => return Ok(None),
+                Err(e) => return Err(Error::storage(e.to_string())),
+            };
+            let content_encoding = resp.header("Content-Encoding").map(str::to_string);
+            let mut body = vec![];
+            resp.into_reader()
+                .read_to_end(&mut body)
+                .map_err(Error::storage)?;
+            decode(content_encoding.as_deref(), body).map(Some)
+        }
+
+        fn hash(&self) -> Result<Option<String>, Error> {
+            match self.request("HEAD", &self.param.key, "", "", vec![]).call() {
+                Ok(resp) => Ok(resp
+                    .header(&format!("x-oss-meta-{}", HASH_META))
+                    .map(str::to_string)),
+                Err(ureq::Error::Status(404, _)) => Ok(None),
+                Err(e) => Err(Error::storage(e.to_string())),
+            }
+        }
+
+        fn hash_of(&self, content_hash: &str) -> String {
+            let param = &self.param;
+            PutOptions {
+                encoding: param.encoding,
+                compressed_key: param.compressed_key.as_deref(),
+                acl: param.acl.as_deref(),
+                content_type: param.content_type.as_deref(),
+                cache_control: param.cache_control.as_deref(),
+                content_disposition: param.content_disposition.as_deref(),
+                storage_class: param.storage_class.as_deref(),
+                metadata: Some(&param.metadata),
+            }
+            .hash(content_hash)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{OssParam, Store, Sts};
+
+        #[test]
+        fn region_of_endpoint() {
+            let store = |param: serde_json::Value| {
+                let param: OssParam = serde_json::from_value(param).unwrap();
+                let sts = Sts {
+                    id: "id".to_string(),
+                    secret: "secret".to_string(),
+                    token: "token".to_string(),
+                };
+                Store::new(param, sts)
+            };
+            let internal = store(serde_json::json!({
+                "bucket": "sls12",
+                "key": "rss.xml",
+                "endpoint": "oss-cn-shanghai-internal.aliyuncs.com",
+                "signature": "v4"
+            }))
+            .unwrap();
+            assert_eq!(internal.region, "cn-shanghai");
+            assert_eq!(
+                internal.url("rss.xml"),
+                "http://sls12.oss-cn-shanghai-internal.aliyuncs.com/rss.xml"
+            );
+            let custom = store(serde_json::json!({
+                "bucket": "sls12",
+                "key": "rss.xml",
+                "endpoint": "feeds.example.com",
+                "region": "cn-hangzhou",
+                "signature": "v4",
+                "https": true
+            }))
+            .unwrap();
+            assert_eq!(custom.region, "cn-hangzhou");
+            assert_eq!(
+                custom.url("rss.xml"),
+                "https://sls12.feeds.example.com/rss.xml"
+            );
+
+            let unscoped = serde_json::json!({
+                "bucket": "sls12",
+                "key": "rss.xml",
+                "endpoint": "feeds.example.com",
+                "signature": "v4"
+            });
+            let err = store(unscoped.clone())
+                .err()
+                .expect("custom domain without region");
+            assert_eq!(err.status(), 400);
+            let mut v1 = unscoped;
+            v1["signature"] = serde_json::json!("v1");
+            assert!(store(v1).is_ok());
+            assert!(store(serde_json::json!({
+                "bucket": "sls12",
+                "key": "rss.xml",
+                "endpoint": "oss-accelerate.aliyuncs.com",
+                "signature": "v4"
+            }))
+            .is_err());
+        }
+    }
+}
+
+pub mod local {
+    use super::FeedStore;
+    use crate::error::Error;
+    use serde::Deserialize;
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    #[derive(Deserialize, Debug)]
+    pub struct LocalParam {
+        pub dir: String,
+        pub key: String,
+    }
+
+    pub struct Store {
+        path: PathBuf,
+        //sidecar file holding the content hash
+        hash_path: PathBuf,
+    }
+
+    impl Store {
+        pub fn new(param: LocalParam) -> Self {
+            let path = PathBuf::from(param.dir).join(param.key);
+            let mut hash_path = path.clone().into_os_string();
+            hash_path.push(".sha256");
+            Store {
+                path,
+                hash_path: hash_path.into(),
+            }
+        }
+    }
+
+    fn read(path: &Path) -> Result<Option<Vec<u8>>, Error> {
+        match fs::read(path) {
+            Ok(body) => Ok(Some(body)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::storage(e)),
+        }
+    }
+
+    impl FeedStore for Store {
+        //files carry no content type
+        fn put(&self, body: Vec<u8>, hash: &str, _: &str) -> Result<(), Error> {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent).map_err(Error::storage)?;
+            }
+            fs::write(&self.path, body).map_err(Error::storage)?;
+            fs::write(&self.hash_path, hash).map_err(Error::storage)
+        }
+
+        fn get(&self) -> Result<Option<Vec<u8>>, Error> {
+            read(&self.path)
+        }
+
+        fn hash(&self) -> Result<Option<String>, Error> {
+            Ok(read(&self.hash_path)?.map(|h| String::from_utf8_lossy(&h).into_owned()))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::super::{open, StorageParam};
+        use std::error::Error;
+        use std::{env, fs};
+
+        #[test]
+        fn put_file() -> Result<(), Box<dyn Error>> {
+            let dir = env::temp_dir().join(format!("gcores_rss_local_{}", std::process::id()));
+            let param: StorageParam = serde_json::from_value(serde_json::json!({
+                "service": "local",
+                "dir": dir.to_str(),
+                "key": "feed/rss.xml"
+            }))?;
+            let store = open(param, None)?;
+            assert_eq!(store.get()?, None);
+            assert_eq!(store.hash()?, None);
+            store.put(b"<rss/>".to_vec(), "abc", "application/xml")?;
+            assert_eq!(fs::read_to_string(dir.join("feed/rss.xml"))?, "<rss/>");
+            assert_eq!(store.get()?, Some(b"<rss/>".to_vec()));
+            assert_eq!(store.hash()?, Some("abc".to_string()));
+            fs::remove_dir_all(dir)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode, objects, oss, s3, Encoding, Object, PutOptions, StorageParam, Sts, HASH_META,
+    };
+    use crate::sign;
+    use chrono::{TimeZone, Utc};
+    use rusoto_core::Region;
+    use std::collections::HashMap;
+    use std::error::Error;
+
+    #[test]
+    fn put_options() -> Result<(), Box<dyn Error>> {
+        let object = || Object {
+            key: "gadio.xml".to_string(),
+            body: b"<rss/>".to_vec(),
+            encoding: Some(Encoding::Gzip),
+        };
+        let s3: StorageParam = serde_json::from_value(serde_json::json!({
+            "service": "s3",
+            "bucket": "feeds",
+            "key": "gadio.xml",
+            "region": "ap-northeast-1",
+            "cache_control": "max-age=300",
+            "storage_class": "STANDARD_IA",
+            "metadata": { "source": "gcores" }
+        }))?;
+        match s3 {
+            StorageParam::S3(param) => {
+                assert_eq!(s3::region(&param)?, Region::ApNortheast1);
+                let req = s3::Store::new(param)?.put_request(object(), "abc", "application/xml");
+                assert_eq!(req.bucket, "feeds");
+                assert_eq!(req.content_type.as_deref(), Some("application/xml"));
+                assert_eq!(req.content_encoding.as_deref(), Some("gzip"));
+                assert_eq!(req.cache_control.as_deref(), Some("max-age=300"));
+                assert_eq!(req.content_disposition, None);
+                assert_eq!(req.storage_class.as_deref(), Some("STANDARD_IA"));
+                let metadata = req.metadata.unwrap_or_default();
+                assert_eq!(metadata["source"], "gcores");
+                assert_eq!(metadata[HASH_META], "abc");
+            }
+            other => panic!("not s3: {:?}", other),
+        }
+
+        let oss: StorageParam = serde_json::from_value(serde_json::json!({
+            "service": "oss",
+            "bucket": "feeds",
+            "key": "gadio.xml",
+            "endpoint": "oss-cn-shanghai.aliyuncs.com",
+            "https": true,
+            "content_type": "application/rss+xml",
+            "content_disposition": "inline",
+            "storage_class": "IA",
+            "metadata": { "Source": "gcores" },
+            "signature": "v4",
+            "region": "cn-hangzhou"
+        }))?;
+        let param = match oss {
+            StorageParam::Oss(param) => param,
+            other => panic!("not oss: {:?}", other),
+        };
+        let sts = Sts {
+            id: "id".to_string(),
+            secret: "secret".to_string(),
+            token: "token".to_string(),
+        };
+        let req = oss::Store::new(param, sts)?.put_request(&object(), "abc", "application/xml");
+        assert_eq!(
+            req.url(),
+            "https://feeds.oss-cn-shanghai.aliyuncs.com/gadio.xml"
+        );
+        assert_eq!(req.header("Content-Type"), Some("application/rss+xml"));
+        assert_eq!(req.header("Content-Encoding"), Some("gzip"));
+        assert_eq!(req.header("Content-Disposition"), Some("inline"));
+        assert_eq!(req.header("Cache-Control"), None);
+
+        //every x-oss-* header sent is covered by the signature
+        let oss_headers: Vec<(String, String)> = req
+            .header_names()
+            .into_iter()
+            .filter(|k| k.to_lowercase().starts_with("x-oss-"))
+            .map(|k| {
+                (
+                    k.to_lowercase(),
+                    req.header(&k).unwrap_or_default().to_string(),
+                )
+            })
+            .collect();
+        let header = |name: &str| {
+            oss_headers
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.as_str())
+        };
+        assert_eq!(header("x-oss-storage-class"), Some("IA"));
+        assert_eq!(header("x-oss-meta-source"), Some("gcores"));
+        assert_eq!(header("x-oss-meta-feed-hash"), Some("abc"));
+        let date = header(sign::OSS_DATE).unwrap_or_default();
+        let now = Utc.datetime_from_str(date, "%Y%m%dT%H%M%SZ")?;
+        let signed = sign::Request {
+            verb: "PUT",
+            bucket: "feeds",
+            key: "gadio.xml",
+            content_md5: req.header("Content-MD5").unwrap_or_default(),
+            content_type: "application/rss+xml",
+            oss_headers: &oss_headers,
+            query: &[],
+        };
+        assert_eq!(
+            req.header("authorization"),
+            Some(sign::v4(&signed, "id", "secret", "cn-hangzhou", now).as_str())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn custom_endpoint() -> Result<(), Box<dyn Error>> {
+        let param = |extra: serde_json::Value| -> Result<s3::S3Param, serde_json::Error> {
+            let mut value = serde_json::json!({ "bucket": "feeds", "key": "gadio.xml" });
+            value
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            serde_json::from_value(value)
+        };
+
+        assert_eq!(s3::region(&param(serde_json::json!({}))?)?, Region::UsEast1);
+        assert_eq!(
+            s3::region(&param(serde_json::json!({
+                "endpoint": "http://127.0.0.1:9000/"
+            }))?)?,
+            Region::Custom {
+                name: "us-east-1".to_string(),
+                endpoint: "http://127.0.0.1:9000".to_string(),
+            }
+        );
+        assert_eq!(
+            s3::region(&param(serde_json::json!({
+                "endpoint": "https://acc.r2.cloudflarestorage.com",
+                "region": "auto"
+            }))?)?,
+            Region::Custom {
+                name: "auto".to_string(),
+                endpoint: "https://acc.r2.cloudflarestorage.com".to_string(),
+            }
+        );
+        let err = s3::region(&param(serde_json::json!({ "region": "mars-1" }))?).unwrap_err();
+        assert_eq!(err.status(), 400);
+        Ok(())
+    }
+
+    #[test]
+    fn compressed_objects() -> Result<(), Box<dyn Error>> {
+        let body = "<rss>机核</rss>".repeat(100).into_bytes();
+        for encoding in [Encoding::Gzip, Encoding::Br] {
+            let encoded = encoding.encode(&body)?;
+            assert!(encoded.len() < body.len());
+            assert_eq!(decode(Some(encoding.name()), encoded)?, body);
+        }
+        assert_eq!(decode(None, body.clone())?, body);
+        assert!(decode(Some("zstd"), body.clone()).is_err());
+
+        let plain = objects("rss.xml", body.clone(), None, Some("rss.xml.gz"))?;
+        assert_eq!(plain.len(), 1);
+        assert_eq!(plain[0].body, body);
+
+        let in_place = objects("rss.xml", body.clone(), Some(Encoding::Br), None)?;
+        assert_eq!(in_place.len(), 1);
+        assert_eq!(in_place[0].key, "rss.xml");
+        assert_eq!(in_place[0].encoding, Some(Encoding::Br));
+
+        let copy = objects(
+            "rss.xml",
+            body.clone(),
+            Some(Encoding::Gzip),
+            Some("rss.xml.gz"),
+        )?;
+        let keys: Vec<_> = copy.iter().map(|o| (o.key.as_str(), o.encoding)).collect();
+        assert_eq!(
+            keys,
+            vec![("rss.xml.gz", Some(Encoding::Gzip)), ("rss.xml", None)]
+        );
+        assert_eq!(copy[1].body, body);
+
+        //turning compression on, moving the copy or changing what the put sends
+        //is a change of the stored feed
+        let gzip = |compressed_key| PutOptions {
+            encoding: Some(Encoding::Gzip),
+            compressed_key,
+            ..Default::default()
+        };
+        let metadata = |v: &str| {
+            let mut m = HashMap::new();
+            m.insert("source".to_string(), v.to_string());
+            m.insert("a".to_string(), "1".to_string());
+            m
+        };
+        let (gcores, other) = (metadata("gcores"), metadata("other"));
+        let hashes = [
+            PutOptions {
+                compressed_key: Some("rss.xml.gz"),
+                metadata: Some(&HashMap::new()),
+                ..Default::default()
+            }
+            .hash("abc"),
+            gzip(None).hash("abc"),
+            gzip(Some("rss.xml.gz")).hash("abc"),
+            PutOptions {
+                encoding: Some(Encoding::Br),
+                ..gzip(Some("rss.xml.gz"))
+            }
+            .hash("abc"),
+            PutOptions {
+                cache_control: Some("max-age=300"),
+                ..Default::default()
+            }
+            .hash("abc"),
+            PutOptions {
+                cache_control: Some("max-age=600"),
+                ..Default::default()
+            }
+            .hash("abc"),
+            PutOptions {
+                content_type: Some("application/rss+xml"),
+                ..Default::default()
+            }
+            .hash("abc"),
+            PutOptions {
+                acl: Some("private"),
+                ..Default::default()
+            }
+            .hash("abc"),
+            PutOptions {
+                content_disposition: Some("inline"),
+                ..Default::default()
+            }
+            .hash("abc"),
+            PutOptions {
+                storage_class: Some("IA"),
+                ..Default::default()
+            }
+            .hash("abc"),
+            PutOptions {
+                metadata: Some(&gcores),
+                ..Default::default()
+            }
+            .hash("abc"),
+            PutOptions {
+                metadata: Some(&other),
+                ..Default::default()
+            }
+            .hash("abc"),
+        ];
+        assert_eq!(hashes[0], "abc");
+        //metadata is hashed in key order, not in the map's iteration order
+        let mut sorted: Vec<_> = gcores.clone().into_iter().collect();
+        sorted.sort();
+        let same: HashMap<_, _> = sorted.into_iter().rev().collect();
+        let metadata_hash = |m| {
+            PutOptions {
+                metadata: Some(m),
+                ..Default::default()
+            }
+            .hash("abc")
+        };
+        assert_eq!(metadata_hash(&same), hashes[10]);
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(!hashes[i + 1..].contains(hash));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,446 @@
+use crate::error::Error;
+use crate::model::{Channel, ItunesCategory};
+use serde::Serialize;
+use std::fmt;
+use url::Url;
+
+//ISO 639-1
+const LANGUAGES: &str =
+    "aa ab ae af ak am an ar as av ay az ba be bg bh bi bm bn bo br bs ca ce ch \
+co cr cs cu cv cy da de dv dz ee el en eo es et eu fa ff fi fj fo fr fy ga gd gl gn gu gv ha he hi \
+ho hr ht hu hy hz ia id ie ig ii ik io is it iu ja jv ka kg ki kj kk kl km kn ko kr ks ku kv kw ky \
+la lb lg li ln lo lt lu lv mg mh mi mk ml mn mr ms mt my na nb nd ne ng nl nn no nr nv ny oc oj om \
+or os pa pi pl ps pt qu rm rn ro ru rw sa sc sd se sg si sk sl sm sn so sq sr ss st su sv sw ta te \
+tg th ti tk tl tn to tr ts tt tw ty ug uk ur uz ve vi vo wa wo xh yi yo za zh zu";
+
+//apple podcasts categories and their subcategories
+const CATEGORIES: [(&str, &[&str]); 19] = [
+    (
+        "Arts",
+        &[
+            "Books",
+            "Design",
+            "Fashion & Beauty",
+            "Food",
+            "Performing Arts",
+            "Visual Arts",
+        ],
+    ),
+    (
+        "Business",
+        &[
+            "Careers",
+            "Entrepreneurship",
+            "Investing",
+            "Management",
+            "Marketing",
+            "Non-Profit",
+        ],
+    ),
+    ("Comedy", &["Comedy Interviews", "Improv", "Stand-Up"]),
+    (
+        "Education",
+        &["Courses", "How To", "Language Learning", "Self-Improvement"],
+    ),
+    ("Fiction", &["Comedy Fiction", "Drama", "Science Fiction"]),
+    ("Government", &[]),
+    ("History", &[]),
+    (
+        "Health & Fitness",
+        &[
+            "Alternative Health",
+            "Fitness",
+            "Medicine",
+            "Mental Health",
+            "Nutrition",
+            "Sexuality",
+        ],
+    ),
+    (
+        "Kids & Family",
+        &[
+            "Education for Kids",
+            "Parenting",
+            "Pets & Animals",
+            "Stories for Kids",
+        ],
+    ),
+    (
+        "Leisure",
+        &[
+            "Animation & Manga",
+            "Automotive",
+            "Aviation",
+            "Crafts",
+            "Games",
+            "Hobbies",
+            "Home & Garden",
+            "Video Games",
+        ],
+    ),
+    (
+        "Music",
+        &["Music Commentary", "Music History", "Music Interviews"],
+    ),
+    (
+        "News",
+        &[
+            "Business News",
+            "Daily News",
+            "Entertainment News",
+            "News Commentary",
+            "Politics",
+            "Sports News",
+            "Tech News",
+        ],
+    ),
+    (
+        "Religion & Spirituality",
+        &[
+            "Buddhism",
+            "Christianity",
+            "Hinduism",
+            "Islam",
+            "Judaism",
+            "Religion",
+            "Spirituality",
+        ],
+    ),
+    (
+        "Science",
+        &[
+            "Astronomy",
+            "Chemistry",
+            "Earth Sciences",
+            "Life Sciences",
+            "Mathematics",
+            "Natural Sciences",
+            "Nature",
+            "Physics",
+            "Social Sciences",
+        ],
+    ),
+    (
+        "Society & Culture",
+        &[
+            "Documentary",
+            "Personal Journals",
+            "Philosophy",
+            "Places & Travel",
+            "Relationships",
+        ],
+    ),
+    (
+        "Sports",
+        &[
+            "Baseball",
+            "Basketball",
+            "Cricket",
+            "Fantasy Sports",
+            "Football",
+            "Golf",
+            "Hockey",
+            "Rugby",
+            "Running",
+            "Soccer",
+            "Swimming",
+            "Tennis",
+            "Volleyball",
+            "Wilderness",
+            "Wrestling",
+        ],
+    ),
+    ("Technology", &[]),
+    ("True Crime", &[]),
+    (
+        "TV & Film",
+        &[
+            "After Shows",
+            "Film History",
+            "Film Interviews",
+            "Film Reviews",
+            "TV Reviews",
+        ],
+    ),
+];
+
+//artwork apple accepts
+const IMAGE_EXTENSIONS: [&str; 3] = [".jpg", ".jpeg", ".png"];
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    //the feed is published, apple may still complain
+    Warning,
+    //the feed is not written
+    Error,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub level: Level,
+    //channel field, e.g. categories[0].subcategories[1]
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            Level::Warning => "warning",
+            Level::Error => "error",
+        };
+        write!(f, "{} channel.{}: {}", level, self.field, self.message)
+    }
+}
+
+#[derive(Default)]
+struct Issues(Vec<Issue>);
+
+impl Issues {
+    fn push(&mut self, level: Level, field: &str, message: impl Into<String>) {
+        self.0.push(Issue {
+            level,
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    //an absolute http(s) url, `Level` when empty
+    fn url(&mut self, field: &str, value: &str, empty: Option<Level>) -> Option<Url> {
+        if value.is_empty() {
+            if let Some(level) = empty {
+                self.push(level, field, "is empty");
+            }
+            return None;
+        }
+        match Url::parse(value) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Some(url),
+            Ok(url) => {
+                self.push(
+                    Level::Error,
+                    field,
+                    format!("unsupported scheme {}", url.scheme()),
+                );
+                None
+            }
+            Err(e) => {
+                self.push(
+                    Level::Error,
+                    field,
+                    format!("{} is not a url: {}", value, e),
+                );
+                None
+            }
+        }
+    }
+
+    //urls the episode paths are appended to
+    fn base_url(&mut self, field: &str, value: &str, empty: Option<Level>) {
+        if self.url(field, value, empty).is_some() && !value.ends_with('/') {
+            self.push(Level::Warning, field, "does not end with /");
+        }
+    }
+}
+
+//everything apple would reject or flag about the channel
+pub fn channel(ch: &Channel) -> Vec<Issue> {
+    let mut issues = Issues::default();
+
+    if ch.title.trim().is_empty() {
+        issues.push(Level::Error, "title", "is empty");
+    }
+    if ch.description.trim().is_empty() {
+        issues.push(Level::Warning, "description", "is empty");
+    }
+    if ch.author.trim().is_empty() {
+        issues.push(Level::Warning, "author", "is empty");
+    }
+
+    if !is_language(&ch.language) {
+        issues.push(
+            Level::Error,
+            "language",
+            format!("{:?} is not an ISO 639-1 code such as zh-cn", ch.language),
+        );
+    }
+
+    if let Some(image) = issues.url("image", &ch.image, Some(Level::Error)) {
+        let path = image.path().to_lowercase();
+        if !IMAGE_EXTENSIONS.iter().any(|ext| path.ends_with(ext)) {
+            issues.push(Level::Error, "image", "artwork must be a .jpg or .png");
+        }
+    }
+    issues.url("link", &ch.link, Some(Level::Warning));
+    issues.base_url("media_base_url", &ch.media_base_url, Some(Level::Error));
+    issues.base_url("web_base_url", &ch.web_base_url, Some(Level::Error));
+    issues.base_url("image_base_url", &ch.image_base_url, None);
+
+    if ch.owner_email.is_empty() {
+        issues.push(
+            Level::Warning,
+            "owner_email",
+            "is empty, apple mails the owner to verify the feed",
+        );
+    } else if !is_email(&ch.owner_email) {
+        issues.push(
+            Level::Error,
+            "owner_email",
+            format!("{} is not an email address", ch.owner_email),
+        );
+    }
+
+    let categories = ch.itunes_categories();
+    if categories.is_empty() {
+        issues.push(Level::Error, "categories", "at least one is required");
+    }
+    for (i, cat) in categories.iter().enumerate() {
+        category(&mut issues, i, cat);
+    }
+
+    issues.0
+}
+
+fn category(issues: &mut Issues, i: usize, cat: &ItunesCategory) {
+    let field = format!("categories[{}]", i);
+    let subs = match CATEGORIES.iter().find(|(name, _)| *name == cat.text) {
+        Some((_, subs)) => subs,
+        None => {
+            issues.push(
+                Level::Error,
+                &field,
+                format!("{} is not an apple category", cat.text),
+            );
+            return;
+        }
+    };
+    for (j, sub) in cat.subcategories.iter().enumerate() {
+        let field = format!("{}.subcategories[{}]", field, j);
+        if !subs.contains(&sub.text.as_str()) {
+            issues.push(
+                Level::Error,
+                &field,
+                format!("{} is not a subcategory of {}", sub.text, cat.text),
+            );
+        }
+        if !sub.subcategories.is_empty() {
+            issues.push(Level::Error, &field, "subcategories nest one level only");
+        }
+    }
+}
+
+//xx or xx-yy
+fn is_language(code: &str) -> bool {
+    let mut parts = code.splitn(2, '-');
+    let lang = parts.next().unwrap_or_default().to_lowercase();
+    let region_ok = match parts.next() {
+        Some(region) => {
+            (2..=8).contains(&region.len()) && region.chars().all(|c| c.is_ascii_alphanumeric())
+        }
+        None => true,
+    };
+    lang.len() == 2 && LANGUAGES.split(' ').any(|l| l == lang) && region_ok
+}
+
+fn is_email(addr: &str) -> bool {
+    match addr.split_once('@') {
+        Some((user, domain)) => {
+            !user.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !addr.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+//logs the warnings, fails on any error
+pub fn check(ch: &Channel) -> Result<(), Error> {
+    let issues = channel(ch);
+    let mut errors = vec![];
+    for issue in issues {
+        match issue.level {
+            Level::Warning => log::warn!("{}", issue),
+            Level::Error => errors.push(format!("channel.{}: {}", issue.field, issue.message)),
+        }
+    }
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(Error::config(format!("invalid {}", errors.join("; "))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel, Level};
+    use crate::model::Channel;
+
+    fn valid() -> Channel {
+        serde_json::from_value(serde_json::json!({
+            "title": "机核电台",
+            "author": "机核",
+            "description": "d",
+            "image": "https://image.gcores.com/logo.PNG",
+            "language": "zh-cn",
+            "categories": [{ "text": "Leisure", "subcategories": [{ "text": "Video Games" }] }],
+            "link": "https://www.gcores.com",
+            "owner_email": "rss@example.com",
+            "media_base_url": "https://alioss.gcores.com/uploads/audio/",
+            "web_base_url": "https://www.gcores.com/radios/",
+            "explicit": "no"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn valid_channel() {
+        let ch = valid();
+        assert!(!ch.explicit);
+        assert_eq!(channel(&ch), vec![]);
+    }
+
+    #[test]
+    fn channel_issues() {
+        let mut ch = valid();
+        ch.language = "chinese".to_string();
+        ch.image = "https://image.gcores.com/logo.webp".to_string();
+        ch.web_base_url = "www.gcores.com/radios/".to_string();
+        ch.media_base_url = "https://alioss.gcores.com/uploads/audio".to_string();
+        ch.owner_email = "rss.example.com".to_string();
+        ch.categories[0].subcategories[0].text = "Travel".to_string();
+        ch.categories
+            .push(serde_json::from_value(serde_json::json!({ "text": "Games" })).unwrap());
+
+        let found: Vec<(Level, String)> = channel(&ch)
+            .into_iter()
+            .map(|i| (i.level, i.field))
+            .collect();
+        let expected: Vec<(Level, String)> = vec![
+            (Level::Error, "language"),
+            (Level::Error, "image"),
+            (Level::Warning, "media_base_url"),
+            (Level::Error, "web_base_url"),
+            (Level::Error, "owner_email"),
+            (Level::Error, "categories[0].subcategories[0]"),
+            (Level::Error, "categories[1]"),
+        ]
+        .into_iter()
+        .map(|(l, f)| (l, f.to_string()))
+        .collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn explicit_flag() {
+        let parse = |v: serde_json::Value| {
+            serde_json::from_value::<Channel>(serde_json::json!({ "explicit": v }))
+                .map(|ch| ch.explicit)
+        };
+        assert!(parse(serde_json::json!(true)).unwrap());
+        assert!(parse(serde_json::json!("yes")).unwrap());
+        assert!(!parse(serde_json::json!("clean")).unwrap());
+        assert!(parse(serde_json::json!("maybe")).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+// local stand-in for https://www.gcores.com/gapi/v1/radios,
+// and for an s3 compatible endpoint to publish into
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use warp::http::{HeaderMap, Method, Response, StatusCode};
+use warp::hyper::body::Bytes;
+use warp::Filter;
+
+pub const PATH: &str = "gapi/v1/radios";
+
+#[derive(Clone)]
+pub enum Page {
+    Fixture(&'static str),
+    Status(u16),
+}
+
+// an object PUT through the s3 api
+#[derive(Clone)]
+pub struct Object {
+    pub body: Vec<u8>,
+    pub headers: HashMap<String, String>,
+}
+
+type Objects = Arc<Mutex<HashMap<String, Object>>>;
+
+pub struct Server {
+    pub addr: SocketAddr,
+    requested: Arc<Mutex<Vec<String>>>,
+    objects: Objects,
+}
+
+impl Server {
+    // pages keyed by their page[offset], unknown offsets answer 404
+    pub fn start(pages: Vec<(u32, Page)>) -> Server {
+        let pages: Arc<HashMap<u32, Page>> = Arc::new(pages.into_iter().collect());
+        let requested = Arc::new(Mutex::new(vec![]));
+        let log = requested.clone();
+
+        let route = warp::path!("gapi" / "v1" / "radios")
+            .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+            .map(move |query: String| {
+                log.lock().unwrap().push(query.clone());
+                let page = offset(&query).and_then(|o| pages.get(&o).cloned());
+                match page {
+                    Some(Page::Fixture(name)) => {
+                        let body = fs::read_to_string(format!("tests/fixtures/{}", name))
+                            .expect("read fixture");
+                        warp::reply::with_status(body, StatusCode::OK)
+                    }
+                    Some(Page::Status(code)) => warp::reply::with_status(
+                        String::new(),
+                        StatusCode::from_u16(code).expect("status code"),
+                    ),
+                    None => warp::reply::with_status(String::new(), StatusCode::NOT_FOUND),
+                }
+            });
+        let objects: Objects = Arc::new(Mutex::new(HashMap::new()));
+        let bucket = objects.clone();
+        // path-style addressing, /{bucket}/{key}
+        let s3 = warp::path!(String / String)
+            .and(warp::method())
+            .and(warp::header::headers_cloned())
+            .and(warp::body::bytes())
+            .map(
+                move |bucket_name: String,
+                      key: String,
+                      method: Method,
+                      headers: HeaderMap,
+                      body: Bytes| {
+                    s3_object(
+                        &bucket,
+                        format!("{}/{}", bucket_name, key),
+                        method,
+                        headers,
+                        body,
+                    )
+                },
+            );
+
+        let (addr, server) = warp::serve(route.or(s3)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        Server {
+            addr,
+            requested,
+            objects,
+        }
+    }
+
+    // s3 `endpoint` of the server
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    // object stored at {bucket}/{key}
+    pub fn object(&self, path: &str) -> Option<Object> {
+        self.objects.lock().unwrap().get(path).cloned()
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}/{}", self.addr, PATH)
+    }
+
+    // raw query strings received so far
+    pub fn requested(&self) -> Vec<String> {
+        self.requested.lock().unwrap().clone()
+    }
+}
+
+// PUT stores the body with its headers, GET and HEAD answer with them
+fn s3_object(
+    objects: &Objects,
+    path: String,
+    method: Method,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response<Vec<u8>> {
+    let mut objects = objects.lock().unwrap();
+    let reply = Response::builder();
+    let reply = match (method, objects.get(&path)) {
+        (Method::PUT, _) => {
+            let headers = headers
+                .iter()
+                .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
+                .collect();
+            objects.insert(
+                path,
+                Object {
+                    body: body.to_vec(),
+                    headers,
+                },
+            );
+            reply.status(StatusCode::OK).body(vec![])
+        }
+        (method @ Method::GET, Some(object)) | (method @ Method::HEAD, Some(object)) => {
+            let mut reply = reply.status(StatusCode::OK);
+            for (k, v) in object.headers.iter().filter(|(k, _)| {
+                k.starts_with("x-amz-meta-") || *k == "content-type" || *k == "content-encoding"
+            }) {
+                reply = reply.header(k.as_str(), v.as_str());
+            }
+            let body = match method {
+                Method::GET => object.body.clone(),
+                _ => vec![],
+            };
+            reply.body(body)
+        }
+        (Method::GET, None) => reply.status(StatusCode::NOT_FOUND).body(
+            b"<Error><Code>NoSuchKey</Code><Message>The specified key does not exist.</Message></Error>"
+                .to_vec(),
+        ),
+        (Method::HEAD, None) => reply.status(StatusCode::NOT_FOUND).body(vec![]),
+        _ => reply.status(StatusCode::METHOD_NOT_ALLOWED).body(vec![]),
+    };
+    reply.expect("s3 response")
+}
+
+fn offset(query: &str) -> Option<u32> {
+    query
+        .replace("%5B", "[")
+        .replace("%5D", "]")
+        .split('&')
+        .find_map(|kv| kv.strip_prefix("page[offset]="))
+        .and_then(|v| v.parse().ok())
+}
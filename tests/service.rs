@@ -1,10 +1,18 @@
+mod mock;
+
 #[cfg(test)]
 mod tests {
-    use gcores_rss::{get, Channel, Param};
+    use super::mock::{Page, Server};
+    use gcores_rss::manifest::{publish_all, Event};
+    use gcores_rss::store::{self, StorageParam};
+    use gcores_rss::{
+        fetch::Memory, get, get_with, publish, stream_with, Channel, Error, Outcome, Param,
+    };
+    use std::sync::Arc;
+    use std::{env, fs};
 
-    #[tokio::test]
-    async fn test_get() {
-        let ch_info = Channel {
+    fn channel() -> Channel {
+        Channel {
             title: "test podcast".to_string(),
             description: "some desc".to_string(),
             image: "http://www.example.com/podcast-icon.jpg".to_string(),
@@ -13,20 +21,387 @@ mod tests {
             owner_name: "some owner".to_string(),
             owner_email: "some@eee.com".to_string(),
             media_base_url: "https://example.com/media/".to_string(),
-            explicit: "true".to_string(),
-            language: "test language".to_string(),
-            category1: "Travel".to_string(),
-            category2: "cook".to_string(),
-            web_base_url: "http::/exm.com/pages/".to_string(),
+            explicit: true,
+            language: "zh-cn".to_string(),
+            category1: "Society & Culture".to_string(),
+            category2: "Places & Travel".to_string(),
+            web_base_url: "http://exm.com/pages/".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn titles(xml: &str) -> Vec<&str> {
+        xml.split("<item>")
+            .skip(1)
+            .filter_map(|item| {
+                let start = item.find("<title>")? + "<title>".len();
+                let end = item.find("</title>")?;
+                Some(&item[start..end])
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_get() {
+        let server = Server::start(vec![(0, Page::Fixture("radios_offset_0.json"))]);
+        let fetch_param = Param {
+            url: server.url(),
+            start: 0u32,
+            size: 3u16,
+            ..Default::default()
+        };
+
+        let xml_str = get(fetch_param, channel()).await.expect("get xml error");
+        assert_eq!(server.requested().len(), 1);
+        assert_eq!(
+            titles(&xml_str),
+            vec![
+                "免费试听集：《伪神》Episode 1",
+                "用这几款桌游来体验穿梭在“生死之间”",
+                "《死亡空间》回来了！GadioNews07.24",
+            ]
+        );
+        assert!(xml_str.contains("<pubDate>Tue, 27 Jul 2021 22:00:00 +0800</pubDate>"));
+        assert!(xml_str.contains("<itunes:duration>01:07:21</itunes:duration>"));
+        assert!(xml_str.contains(
+            r#"url="https://example.com/media/cd22ef44-4a22-4e3b-8b6d-87fc52a15409.mp3""#
+        ));
+        assert!(xml_str.contains("<link>http://exm.com/pages/139339</link>"));
+    }
+
+    #[tokio::test]
+    async fn test_get_pages() {
+        let server = Server::start(vec![
+            (0, Page::Fixture("radios_offset_0.json")),
+            (3, Page::Fixture("radios_offset_3.json")),
+        ]);
+        let fetch_param = Param {
+            url: server.url(),
+            start: 0u32,
+            size: 3u16,
+            limit: Some(10),
+            delay: Some(0),
+            ..Default::default()
+        };
+
+        let xml_str = get(fetch_param, channel()).await.expect("get xml error");
+        //links.next points at www.gcores.com, it must be sent to the local server
+        assert_eq!(server.requested().len(), 2);
+        //the last radio of page 2 has no media and is left out
+        assert_eq!(titles(&xml_str).len(), 4);
+        assert_eq!(titles(&xml_str)[3], "GadioNews07.17");
+        assert!(xml_str.contains("<itunes:duration>01:02:05</itunes:duration>"));
+        assert!(!xml_str.contains("只有文字的节目"));
+        //djs of the radio are resolved from `included`
+        assert!(xml_str.contains("<itunes:author>西蒙, 四十二</itunes:author>"));
+        //no image_base_url, so no bare file names as artwork
+        assert!(xml_str.contains(r#"<podcast:person role="host">西蒙</podcast:person>"#));
+        assert!(!xml_str.contains("a1b2c3d4.jpg"));
+        assert!(xml_str.contains(r#"<podcast:person role="host">四十二</podcast:person>"#));
+    }
+
+    #[tokio::test]
+    async fn test_get_upstream_error() {
+        let server = Server::start(vec![
+            (0, Page::Fixture("radios_offset_0.json")),
+            (3, Page::Status(503)),
+        ]);
+        let fetch_param = Param {
+            url: server.url(),
+            start: 0u32,
+            size: 3u16,
+            limit: Some(10),
+            delay: Some(0),
+            ..Default::default()
+        };
+
+        let err = get(fetch_param, channel()).await.unwrap_err();
+        assert!(matches!(err, Error::Http(_)), "{}", err);
+        assert_eq!(err.status(), 502);
+        assert_eq!(server.requested().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_malformed() {
+        let server = Server::start(vec![(0, Page::Fixture("malformed.json"))]);
+        let fetch_param = Param {
+            url: server.url(),
+            start: 0u32,
+            size: 3u16,
+            ..Default::default()
+        };
+
+        let err = get(fetch_param, channel()).await.unwrap_err();
+        assert!(matches!(err, Error::Decode(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_get_with_fixture() {
+        let fetch_param = Param {
+            url: "http://fake/gapi/v1/radios".to_owned(),
+            start: 0u32,
+            size: 3u16,
             ..Default::default()
         };
+        let json = fs::read_to_string("api_response.json").expect("read fixture");
+
+        //runs on a worker thread, so the future must be Send
+        let xml_str = tokio::spawn(async move {
+            get_with(Memory::new(json), fetch_param, channel())
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .expect("join error")
+        .expect("get xml error");
+        assert_eq!(titles(&xml_str).len(), 3);
+        assert!(
+            xml_str.contains("https://example.com/media/5d499753-a4d1-409e-9a1a-d6bb0e23e7a1.mp3")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_to_file() {
+        let fetch_param = Param {
+            url: "http://fake/gapi/v1/radios".to_owned(),
+            start: 0u32,
+            size: 3u16,
+            ..Default::default()
+        };
+        let json = fs::read_to_string("api_response.json").expect("read fixture");
+        let path = env::temp_dir().join(format!("gcores_rss_stream_{}.xml", std::process::id()));
+
+        let mut file = fs::File::create(&path).expect("create file");
+        stream_with(
+            Memory::new(json.clone()),
+            fetch_param.clone(),
+            channel(),
+            &mut file,
+        )
+        .await
+        .expect("stream xml error");
+        let streamed = fs::read_to_string(&path).expect("read streamed");
+        fs::remove_file(&path).expect("remove file");
+
+        let xml_str = get_with(Memory::new(json), fetch_param, channel())
+            .await
+            .expect("get xml error");
+        assert!(streamed.starts_with("<?xml"));
+        assert!(streamed.ends_with("</rss>"));
+        assert_eq!(titles(&streamed), titles(&xml_str));
+    }
+
+    #[tokio::test]
+    async fn test_get_checks_channel() {
         let fetch_param = Param {
-            url: "https://www.gcores.com/gapi/v1/radios".to_owned(),
-            start: 0u16,
+            url: "http://fake/gapi/v1/radios".to_owned(),
+            start: 0u32,
+            size: 3u16,
+            ..Default::default()
+        };
+        let json = fs::read_to_string("api_response.json").expect("read fixture");
+        let fake = Arc::new(Memory::new(json));
+        let invalid = || Channel {
+            owner_email: "not an email".to_string(),
+            ..channel()
+        };
+
+        let err = get_with(fake.clone(), fetch_param.clone(), invalid())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Config(_)), "{}", err);
+        assert!(err.to_string().contains("channel.owner_email"), "{}", err);
+        let mut out = vec![];
+        let err = stream_with(fake.clone(), fetch_param, invalid(), &mut out)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Config(_)), "{}", err);
+        //nothing is fetched or written for a channel that would not publish
+        assert!(fake.requested().is_empty());
+        assert!(out.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_publish_accumulates() {
+        let dir = env::temp_dir().join(format!("gcores_rss_publish_{}", std::process::id()));
+        let storage_param = || -> StorageParam {
+            serde_json::from_value(serde_json::json!({
+                "service": "local",
+                "dir": dir.to_str(),
+                "key": "gadio.xml"
+            }))
+            .expect("storage param")
+        };
+        let fetch_param = |url: String, max_items: Option<u32>| Param {
+            url,
+            start: 0u32,
             size: 3u16,
+            max_items,
+            ..Default::default()
+        };
+
+        //an older run only saw the episodes of the second page
+        let older = Server::start(vec![(0, Page::Fixture("radios_offset_3.json"))]);
+        let store = store::open(storage_param(), None).expect("open store");
+        publish(fetch_param(older.url(), None), channel(), store.clone())
+            .await
+            .expect("first publish");
+
+        let newer = Server::start(vec![(0, Page::Fixture("radios_offset_0.json"))]);
+        publish(fetch_param(newer.url(), None), channel(), store.clone())
+            .await
+            .expect("second publish");
+        let xml_str = fs::read_to_string(dir.join("gadio.xml")).expect("read feed");
+        assert_eq!(
+            titles(&xml_str),
+            vec![
+                "免费试听集：《伪神》Episode 1",
+                "用这几款桌游来体验穿梭在“生死之间”",
+                "《死亡空间》回来了！GadioNews07.24",
+                "GadioNews07.17",
+            ]
+        );
+
+        //same episodes again, capped
+        let outcome = publish(fetch_param(newer.url(), Some(2)), channel(), store.clone())
+            .await
+            .expect("third publish");
+        assert_eq!(outcome, Outcome::Published);
+        let xml_str = fs::read_to_string(dir.join("gadio.xml")).expect("read feed");
+        assert_eq!(titles(&xml_str).len(), 2);
+
+        //nothing new upstream, the stored feed is left alone
+        let outcome = publish(fetch_param(newer.url(), Some(2)), channel(), store.clone())
+            .await
+            .expect("fourth publish");
+        assert_eq!(outcome, Outcome::Unchanged);
+        assert_eq!(
+            fs::read_to_string(dir.join("gadio.xml")).expect("read feed"),
+            xml_str
+        );
+
+        //a stored feed that does not parse is a decode error, not an empty history
+        let broken: [&[u8]; 3] = [
+            b"<rss><channel><item><pubDate>yesterday</pubDate></item></channel></rss>",
+            b"<rss><channel></item></rss>",
+            b"<rss>\xff</rss>",
+        ];
+        for body in broken.iter() {
+            fs::write(dir.join("gadio.xml"), body).expect("break feed");
+            let err = publish(fetch_param(newer.url(), None), channel(), store.clone())
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::Decode(_)), "{}", err);
+            assert_eq!(fs::read(dir.join("gadio.xml")).expect("read feed"), *body);
+        }
+
+        fs::remove_dir_all(dir).expect("clean up");
+    }
+
+    #[tokio::test]
+    async fn test_publish_s3_endpoint() {
+        //signed with made up keys, the mock does not check them
+        env::set_var("AWS_ACCESS_KEY_ID", "minio");
+        env::set_var("AWS_SECRET_ACCESS_KEY", "minio123");
+        let server = Server::start(vec![(0, Page::Fixture("radios_offset_0.json"))]);
+        let storage_param: StorageParam = serde_json::from_value(serde_json::json!({
+            "service": "s3",
+            "bucket": "feeds",
+            "key": "gadio.xml",
+            "endpoint": server.endpoint(),
+            "encoding": "gzip",
+            "compressed_key": "gadio.xml.gz",
+            "metadata": { "source": "gcores" }
+        }))
+        .expect("storage param");
+        let store = store::open(storage_param, None).expect("open store");
+        let fetch_param = || Param {
+            url: server.url(),
+            start: 0u32,
+            size: 3u16,
+            ..Default::default()
         };
 
-        let xml_str = get(fetch_param, ch_info).await.expect("get xml error");
-        println!("{}", xml_str);
+        let outcome = publish(fetch_param(), channel(), store.clone())
+            .await
+            .expect("first publish");
+        assert_eq!(outcome, Outcome::Published);
+        let plain = server.object("feeds/gadio.xml").expect("plain feed");
+        let xml_str = String::from_utf8(plain.body).expect("utf8 feed");
+        assert_eq!(titles(&xml_str).len(), 3);
+        assert_eq!(plain.headers["content-type"], "application/xml");
+        assert_eq!(plain.headers["x-amz-meta-source"], "gcores");
+        assert!(plain.headers.contains_key("x-amz-meta-feed-hash"));
+        let copy = server
+            .object("feeds/gadio.xml.gz")
+            .expect("compressed copy");
+        assert_eq!(copy.headers["content-encoding"], "gzip");
+        assert!(copy.body.starts_with(&[0x1f, 0x8b]));
+
+        //read back through GET and HEAD, nothing changed upstream
+        let outcome = publish(fetch_param(), channel(), store.clone())
+            .await
+            .expect("second publish");
+        assert_eq!(outcome, Outcome::Unchanged);
+    }
+
+    #[tokio::test]
+    async fn test_publish_manifest() {
+        let dir = env::temp_dir().join(format!("gcores_rss_manifest_{}", std::process::id()));
+        let server = Server::start(vec![(0, Page::Fixture("radios_offset_0.json"))]);
+        let down = Server::start(vec![(0, Page::Status(500))]);
+        let feed = |name: &str, url: String, extra: serde_json::Value| {
+            let mut param = serde_json::json!({ "url": url, "start": 0, "size": 3 });
+            param
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            serde_json::json!({
+                "name": name,
+                "channel": { "title": "t", "author": "a", "description": "d",
+                    "image": "https://example.com/i.jpg", "categories": [{ "text": "Leisure" }],
+                    "language": "zh-cn", "link": "https://example.com", "owner_name": "o",
+                    "owner_email": "o@example.com", "explicit": "false",
+                    "media_base_url": "https://example.com/media/",
+                    "web_base_url": "https://example.com/radios/" },
+                "param": param,
+                "storage_param": { "service": "local", "dir": dir.to_str(), "key": format!("{}.xml", name) }
+            })
+        };
+        let event = serde_json::json!({ "feeds": [
+            feed("all", server.url(), serde_json::json!({})),
+            feed("news", server.url(), serde_json::json!({ "filter": { "include": ["GadioNews"] } })),
+            feed("json", server.url(), serde_json::json!({ "format": "json" })),
+            feed("down", down.url(), serde_json::json!({})),
+        ]});
+        let feeds = match Event::from_value(event).expect("manifest") {
+            Event::Many(feeds) => feeds,
+            Event::One(_) => panic!("expected a manifest"),
+        };
+
+        let reports = publish_all(feeds, None).await;
+        //the three feeds on the same api settings share one request
+        assert_eq!(server.requested().len(), 1);
+        let outcomes: Vec<Option<Outcome>> = reports.iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                Some(Outcome::Published),
+                Some(Outcome::Published),
+                Some(Outcome::Published),
+                None
+            ]
+        );
+        assert_eq!(reports[3].status, 502);
+        assert!(reports[3].error.is_some());
+
+        let all = fs::read_to_string(dir.join("all.xml")).expect("read all");
+        assert_eq!(titles(&all).len(), 3);
+        let news = fs::read_to_string(dir.join("news.xml")).expect("read news");
+        assert_eq!(titles(&news), vec!["《死亡空间》回来了！GadioNews07.24"]);
+        let json = fs::read_to_string(dir.join("json.xml")).expect("read json");
+        assert!(json.contains("https://jsonfeed.org/version/1.1"));
+
+        fs::remove_dir_all(dir).expect("clean up");
     }
 }